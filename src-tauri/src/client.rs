@@ -1,32 +1,133 @@
-use etcd_client::{Client, ConnectOptions};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use etcd_client::{Certificate, Client, ConnectOptions, Identity, KeyValue, TlsOptions};
 use serde::{Deserialize, Serialize};
 
-use crate::config::Profile;
+use crate::config::{Profile, TlsConfig};
+
+/// How raw etcd bytes are represented as text
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Base64,
+    Hex,
+}
+
+impl Encoding {
+    /// Encode bytes as UTF-8 text when possible, falling back to base64
+    pub fn encode_auto(bytes: Vec<u8>) -> (String, Encoding) {
+        match String::from_utf8(bytes) {
+            Ok(text) => (text, Encoding::Utf8),
+            Err(e) => (BASE64_STANDARD.encode(e.into_bytes()), Encoding::Base64),
+        }
+    }
+
+    pub fn decode(self, text: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Base64 => BASE64_STANDARD
+                .decode(text)
+                .map_err(|e| format!("Invalid base64 data: {e}")),
+            Encoding::Hex => hex::decode(text).map_err(|e| format!("Invalid hex data: {e}")),
+        }
+    }
+}
 
 /// Represents a key-value pair from etcd
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// Keys and values that are not valid UTF-8 are carried as base64, as told by
+/// `key_encoding` and `value_encoding`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Item {
     pub key: String,
     pub value: String,
+    #[serde(default)]
+    pub key_encoding: Encoding,
+    #[serde(default)]
+    pub value_encoding: Encoding,
     pub version: i64,
     pub create_revision: i64,
     pub mod_revision: i64,
     pub lease: i64,
 }
 
+impl From<KeyValue> for Item {
+    fn from(kv: KeyValue) -> Self {
+        let (version, create_revision, mod_revision, lease) = (
+            kv.version(),
+            kv.create_revision(),
+            kv.mod_revision(),
+            kv.lease(),
+        );
+        let (key, value) = kv.into_key_value();
+        let (key, key_encoding) = Encoding::encode_auto(key);
+        let (value, value_encoding) = Encoding::encode_auto(value);
+        Item {
+            key,
+            value,
+            key_encoding,
+            value_encoding,
+            version,
+            create_revision,
+            mod_revision,
+            lease,
+        }
+    }
+}
+
+/// A key without its value, as returned by keys-only listings
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ItemKey {
+    pub key: String,
+    #[serde(default)]
+    pub key_encoding: Encoding,
+}
+
+impl From<Vec<u8>> for ItemKey {
+    fn from(key: Vec<u8>) -> Self {
+        let (key, key_encoding) = Encoding::encode_auto(key);
+        ItemKey { key, key_encoding }
+    }
+}
+
+impl From<KeyValue> for ItemKey {
+    fn from(kv: KeyValue) -> Self {
+        ItemKey::from(kv.into_key_value().0)
+    }
+}
+
 pub async fn new_connect(profile: &Profile) -> Result<etcd_client::Client, String> {
-    log::info!("Connecting to etcd with profile: {}", profile.name);
     let endpoints: Vec<String> = profile
         .endpoints
         .iter()
         .map(|endpoint| format!("{}:{}", endpoint.host, endpoint.port))
         .collect();
+    connect_endpoints(profile, endpoints).await
+}
+
+/// Connect to the given endpoints with the credentials and options of `profile`
+pub async fn connect_endpoints(
+    profile: &Profile,
+    endpoints: Vec<String>,
+) -> Result<etcd_client::Client, String> {
+    log::info!(
+        "Connecting to etcd with profile: {} ({:?})",
+        profile.name,
+        endpoints
+    );
 
     // Build connection options
     let mut options = ConnectOptions::new();
-    if let Some((username, _)) = &profile.user {
+    if let Some((username, password)) = &profile.user {
+        if password.is_empty() && profile.password_ref.is_some() {
+            return Err(format!(
+                "The password of profile {} is in the locked secret store, unlock it first",
+                profile.name
+            ));
+        }
         log::debug!("Using authentication for user: {}", username);
-        options = options.with_user(username, profile.user.as_ref().unwrap().1.as_str());
+        options = options.with_user(username, password.as_str());
     }
     if let Some(timeout) = profile.timeout_ms {
         options = options.with_timeout(std::time::Duration::from_millis(timeout));
@@ -34,6 +135,17 @@ pub async fn new_connect(profile: &Profile) -> Result<etcd_client::Client, Strin
     if let Some(connect_timeout) = profile.connect_timeout_ms {
         options = options.with_connect_timeout(std::time::Duration::from_millis(connect_timeout));
     }
+    if let Some(tls) = &profile.tls {
+        // Scheme-less endpoints get `https://` once TLS is set, an explicit `http://` is a mistake
+        if let Some(endpoint) = endpoints.iter().find(|e| e.starts_with("http://")) {
+            return Err(format!(
+                "Endpoint {endpoint} of profile {} uses http:// but TLS is enabled",
+                profile.name
+            ));
+        }
+        log::debug!("Using TLS for profile: {}", profile.name);
+        options = options.with_tls(build_tls_options(tls)?);
+    }
 
     Client::connect(endpoints, Some(options))
         .await
@@ -43,6 +155,23 @@ pub async fn new_connect(profile: &Profile) -> Result<etcd_client::Client, Strin
         })
 }
 
+fn build_tls_options(tls: &TlsConfig) -> Result<TlsOptions, String> {
+    let material = tls.load()?;
+
+    let mut options = TlsOptions::new();
+    if let Some(ca_cert) = material.ca_cert {
+        options = options.ca_certificate(Certificate::from_pem(ca_cert));
+    }
+    if let Some((cert, key)) = material.identity {
+        options = options.identity(Identity::from_pem(cert, key));
+    }
+    if let Some(server_name) = material.server_name {
+        options = options.domain_name(server_name);
+    }
+
+    Ok(options)
+}
+
 pub fn should_refresh<T>(res: &Result<T, etcd_client::Error>) -> bool {
     match res {
         Err(etcd_client::Error::GRpcStatus(status)) => {
@@ -52,3 +181,43 @@ pub fn should_refresh<T>(res: &Result<T, etcd_client::Error>) -> bool {
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_utf8_as_text() {
+        assert_eq!(
+            Encoding::encode_auto("/app/h\u{e9}llo".as_bytes().to_vec()),
+            ("/app/h\u{e9}llo".to_string(), Encoding::Utf8)
+        );
+        assert_eq!(
+            Encoding::encode_auto(Vec::new()),
+            (String::new(), Encoding::Utf8)
+        );
+    }
+
+    #[test]
+    fn encodes_binary_as_base64() {
+        let bytes = vec![0xff, 0x00, 0x80];
+        let (text, encoding) = Encoding::encode_auto(bytes.clone());
+        assert_eq!((text.as_str(), encoding), ("/wCA", Encoding::Base64));
+        assert_eq!(encoding.decode(&text), Ok(bytes));
+    }
+
+    #[test]
+    fn decodes_every_encoding() {
+        assert_eq!(Encoding::Utf8.decode("a/b"), Ok(b"a/b".to_vec()));
+        assert_eq!(Encoding::Base64.decode("YS9i"), Ok(b"a/b".to_vec()));
+        assert_eq!(Encoding::Hex.decode("612F62"), Ok(b"a/b".to_vec()));
+        assert_eq!(Encoding::Hex.decode(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn rejects_invalid_encoded_text() {
+        assert!(Encoding::Base64.decode("not base64!").is_err());
+        assert!(Encoding::Hex.decode("abc").is_err());
+        assert!(Encoding::Hex.decode("zz").is_err());
+    }
+}
@@ -1,9 +1,28 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::{fmt::Display, time::Duration};
 use tauri::Manager;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+use crate::secrets::SecretStore;
+
+mod migrate;
+mod portable;
+
+use migrate::{MigrateError, SCHEMA_VERSION};
+pub use portable::{
+    NameCollision, ProfileDraft, ProfileImportResult, parse_bundle, parse_env_text,
+    profile_from_etcdctl_env,
+};
+
+// Number of previous config files kept in the backup directory
+const CONFIG_BACKUP_COUNT: usize = 10;
+const CONFIG_BACKUP_DIR_NAME: &str = "backups";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppConfig {
+    // Always `SCHEMA_VERSION` once loaded, older files are migrated first
+    #[serde(default)]
+    pub schema_version: u32,
     pub profiles: Vec<Profile>,
     pub current_profile: Option<String>,
     pub color_theme: ColorTheme,
@@ -17,6 +36,22 @@ pub struct AppConfig {
     pub update_check_schedule: UpdateCheckSchedule,
 }
 
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            schema_version: SCHEMA_VERSION,
+            profiles: Vec::new(),
+            current_profile: None,
+            color_theme: ColorTheme::default(),
+            font_family_body: None,
+            font_family_mono: None,
+            kv_load_method: KvLoadMethod::default(),
+            update_channel: UpdateChannel::default(),
+            update_check_schedule: UpdateCheckSchedule::default(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, strum::Display)]
 pub enum UpdateChannel {
     Stable,
@@ -61,20 +96,90 @@ impl UpdateCheckSchedule {
 pub struct Profile {
     pub name: String,
     pub endpoints: Vec<Endpoint>,
+    // (username, password), the password is kept in the secret store on disk
     pub user: Option<(String, String)>,
+    // Key of the password in the secret store, set when the profile has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_ref: Option<String>,
     pub timeout_ms: Option<u64>,
     pub connect_timeout_ms: Option<u64>,
     pub locked: Option<bool>,
-    // The metric path for the cluster endpoints. By default usually `/metrics`
-    #[serde(default = "default_metrics_path")]
+    // The metric path for the cluster endpoints, usually `/metrics`. None disables metrics
     pub metrics_path: Option<String>,
+    // TLS settings, every endpoint is connected to over TLS when set, so none may be `http://`
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+impl Profile {
+    /// Whether a client connected with `other` can be used for this profile
+    pub fn same_connection(&self, other: &Profile) -> bool {
+        self.endpoints == other.endpoints
+            && self.user == other.user
+            && self.timeout_ms == other.timeout_ms
+            && self.connect_timeout_ms == other.connect_timeout_ms
+            && self.tls == other.tls
+    }
 }
 
-fn default_metrics_path() -> Option<String> {
-    Some("/metrics".to_string())
+/// PEM files used to secure the connection to a cluster.
+///
+/// `client_cert_path` and `client_key_path` must be set together for mutual TLS.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TlsConfig {
+    pub ca_cert_path: Option<String>,
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
+    // Overrides the name used to verify the server certificate
+    pub server_name: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// PEM contents loaded from the paths of a [`TlsConfig`]
+pub struct TlsMaterial {
+    pub ca_cert: Option<Vec<u8>>,
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+    pub server_name: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn load(&self) -> Result<TlsMaterial, String> {
+        fn read_pem(path: &str, what: &str) -> Result<Vec<u8>, String> {
+            std::fs::read(path).map_err(|e| format!("Failed to read {what} from {path}: {e}"))
+        }
+
+        fn non_empty(path: &Option<String>) -> Option<&str> {
+            path.as_deref().filter(|p| !p.trim().is_empty())
+        }
+
+        let ca_cert = non_empty(&self.ca_cert_path)
+            .map(|path| read_pem(path, "CA certificate"))
+            .transpose()?;
+
+        let identity = match (
+            non_empty(&self.client_cert_path),
+            non_empty(&self.client_key_path),
+        ) {
+            (Some(cert), Some(key)) => Some((
+                read_pem(cert, "client certificate")?,
+                read_pem(key, "client key")?,
+            )),
+            (None, None) => None,
+            _ => {
+                return Err(
+                    "Client certificate and client key must be provided together".to_string(),
+                );
+            }
+        };
+
+        Ok(TlsMaterial {
+            ca_cert,
+            identity,
+            server_name: self.server_name.clone().filter(|s| !s.is_empty()),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Endpoint {
     pub host: String,
     pub port: u16,
@@ -106,37 +211,429 @@ impl AppConfig {
             .map_err(|e| e.to_string())
     }
 
-    pub fn from_file(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+    /// Load the config at `path`, migrated to the current schema.
+    ///
+    /// If the file cannot be parsed, it is set aside and the latest valid backup is
+    /// loaded instead, or the default config if there is none. A file written by a newer
+    /// build is an error instead, as saving over it would lose what this build ignores.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
         let path = path.as_ref();
-        if path.exists() {
-            log::debug!("Loading config from: {:?}", path);
-            let file = std::fs::File::open(path)?;
-            let reader = std::io::BufReader::new(file);
-            let config: AppConfig = serde_json::from_reader(reader)?;
-            Ok(config)
-        } else {
+        if !path.exists() {
             log::info!("Config file not found at {:?}, using default", path);
-            Ok(AppConfig::default())
+            return Ok(AppConfig::default());
+        }
+
+        log::debug!("Loading config from: {:?}", path);
+        let content =
+            std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let err = match Self::parse(&content) {
+            Ok(config) => return Ok(config),
+            Err(err @ MigrateError::Newer(_)) => {
+                log::error!("Refusing to load config from {:?}: {}", path, err);
+                return Err(err.into());
+            }
+            Err(MigrateError::Invalid(err)) => err,
+        };
+        log::error!("Failed to load config from {:?}: {}", path, err);
+
+        // Keep the broken file around, the next save would overwrite it
+        let mut corrupt_path = path.as_os_str().to_owned();
+        corrupt_path.push(".corrupt");
+        if let Err(e) = std::fs::copy(path, &corrupt_path) {
+            log::error!("Failed to set the broken config aside: {}", e);
+        }
+
+        for backup in Self::backup_paths(path) {
+            let config = std::fs::read(&backup)
+                .map_err(|e| MigrateError::Invalid(e.to_string()))
+                .and_then(|content| Self::parse(&content));
+            match config {
+                Ok(config) => {
+                    log::warn!("Recovered config from backup {:?}", backup);
+                    return Ok(config);
+                }
+                Err(e) => log::warn!("Skipping invalid config backup {:?}: {}", backup, e),
+            }
+        }
+        log::warn!("No valid config backup found, using default");
+        Ok(AppConfig::default())
+    }
+
+    fn parse(content: &[u8]) -> Result<Self, MigrateError> {
+        let mut value: serde_json::Value =
+            serde_json::from_slice(content).map_err(|e| MigrateError::Invalid(e.to_string()))?;
+        migrate::migrate(&mut value)?;
+        serde_json::from_value(value).map_err(|e| MigrateError::Invalid(e.to_string()))
+    }
+
+    fn backup_dir(path: &std::path::Path) -> std::path::PathBuf {
+        path.with_file_name(CONFIG_BACKUP_DIR_NAME)
+    }
+
+    /// Backups of the config at `path`, newest first
+    fn backup_paths(path: &std::path::Path) -> Vec<std::path::PathBuf> {
+        let Ok(entries) = std::fs::read_dir(Self::backup_dir(path)) else {
+            return Vec::new();
+        };
+        let mut backups: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|backup| {
+                backup
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("config-") && name.ends_with(".json"))
+            })
+            .collect();
+        // Names embed a sortable timestamp
+        backups.sort_unstable_by(|a, b| b.cmp(a));
+        backups
+    }
+
+    /// Copy the config currently at `path` to the backup directory, keeping only the
+    /// latest [`CONFIG_BACKUP_COUNT`] backups
+    fn backup(path: &std::path::Path) -> Result<(), String> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+        };
+        match Self::parse(&content) {
+            // A broken file is not worth pushing a good backup out for
+            Err(_) => {
+                log::warn!("Not backing up invalid config at {:?}", path);
+                return Ok(());
+            }
+            // Passwords are being moved to the secret store, do not leave copies behind
+            Ok(config) if config.has_plaintext_passwords() => {
+                log::info!("Not backing up config with plaintext passwords");
+                return Ok(());
+            }
+            Ok(_) => (),
+        }
+
+        let dir = Self::backup_dir(path);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create backup directory {:?}: {}", dir, e))?;
+        let name = format!(
+            "config-{}.json",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+        );
+        std::fs::write(dir.join(name), content)
+            .map_err(|e| format!("Failed to write config backup: {}", e))?;
+
+        for old in Self::backup_paths(path)
+            .into_iter()
+            .skip(CONFIG_BACKUP_COUNT)
+        {
+            log::debug!("Removing old config backup {:?}", old);
+            if let Err(e) = std::fs::remove_file(&old) {
+                log::warn!("Failed to remove old config backup {:?}: {}", old, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the config to `path` atomically, backing up the previous one.
+    ///
+    /// The config is written to a temporary file first and renamed over `path`, so a
+    /// crash leaves either the old or the new config, never a truncated one.
+    pub fn save(&self, path: &std::path::Path) -> Result<(), String> {
+        use std::io::Write;
+
+        let parent = path.parent().ok_or(format!(
+            "Failed to determine parent directory for config path: {:?}",
+            path
+        ))?;
+        std::fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed to create config directory: {}", err))?;
+
+        let mut value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        value["schema_version"] = SCHEMA_VERSION.into();
+        let content = serde_json::to_vec_pretty(&value)
+            .map_err(|e| format!("Failed to write config: {}", e))?;
+
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = std::path::PathBuf::from(tmp_path);
+        let write_tmp = || -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            file.write_all(&content)?;
+            file.sync_all()
+        };
+        write_tmp().map_err(|e| {
+            log::error!("Failed to write config file at {:?}: {}", &tmp_path, e);
+            format!("Failed to write config: {}", e)
+        })?;
+
+        // A failed backup should not prevent saving
+        if let Err(e) = Self::backup(path) {
+            log::error!("Failed to back up config: {}", e);
+        }
+        std::fs::rename(&tmp_path, path).map_err(|e| format!("Failed to replace config: {}", e))?;
+
+        // The rename is only durable once the directory entry is
+        #[cfg(unix)]
+        {
+            if let Err(e) = std::fs::File::open(parent).and_then(|dir| dir.sync_all()) {
+                log::warn!("Failed to sync config directory {:?}: {}", parent, e);
+            }
         }
+        Ok(())
+    }
+
+    /// Whether some profile still has its password in plaintext, from before the secret store
+    pub fn has_plaintext_passwords(&self) -> bool {
+        self.profiles.iter().any(|profile| {
+            profile.password_ref.is_none()
+                && profile
+                    .user
+                    .as_ref()
+                    .is_some_and(|(_, password)| !password.is_empty())
+        })
+    }
+
+    /// Fill in the passwords of the profiles from `store`
+    pub fn resolve_secrets(&mut self, store: &dyn SecretStore) -> Result<(), String> {
+        for profile in &mut self.profiles {
+            let (Some((_, password)), Some(id)) = (&mut profile.user, &profile.password_ref) else {
+                continue;
+            };
+            match store.get(id)? {
+                Some(secret) => *password = secret,
+                None => log::warn!(
+                    "Password of profile {} not found in the secret store",
+                    profile.name
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the passwords of the profiles to `store`.
+    ///
+    /// Profiles get a reference to their password, and the returned copy of the config,
+    /// the one to write to disk, has no password left. Secrets of profiles that no longer
+    /// exist in `self` but did in `previous` are deleted.
+    ///
+    /// While `store` is locked, nothing is written to it and passwords stay as they are.
+    pub fn store_secrets(
+        &mut self,
+        previous: &AppConfig,
+        store: &mut dyn SecretStore,
+    ) -> Result<AppConfig, String> {
+        if !store.is_unlocked() {
+            // Passwords behind a reference could not be resolved and are empty, so any
+            // password here was typed in meanwhile. Keep it in plaintext, it is moved to
+            // the store once unlocked (see `AppState::load_secrets`)
+            for profile in &mut self.profiles {
+                let typed = profile
+                    .user
+                    .as_ref()
+                    .is_some_and(|(_, password)| !password.is_empty());
+                if typed || profile.user.is_none() {
+                    profile.password_ref = None;
+                }
+            }
+            log::info!("Secret store is locked, passwords are kept as is until it is unlocked");
+            return Ok(self.clone());
+        }
+
+        let mut used_refs: HashSet<String> = self
+            .profiles
+            .iter()
+            .filter_map(|profile| profile.password_ref.clone())
+            .collect();
+
+        for profile in &mut self.profiles {
+            match &profile.user {
+                Some((_, password)) if !password.is_empty() => {
+                    let id = match &profile.password_ref {
+                        Some(id) => id.clone(),
+                        None => {
+                            let id = unique_ref(&profile.name, &used_refs);
+                            used_refs.insert(id.clone());
+                            id
+                        }
+                    };
+                    if store.get(&id)?.as_deref() != Some(password.as_str()) {
+                        store.set(&id, password)?;
+                    }
+                    profile.password_ref = Some(id);
+                }
+                // An empty password is either none at all, or one the locked store could
+                // not resolve, keep the reference in both cases
+                Some(_) => (),
+                None => {
+                    if let Some(id) = profile.password_ref.take() {
+                        used_refs.remove(&id);
+                        store.delete(&id)?;
+                    }
+                }
+            }
+        }
+
+        for profile in &previous.profiles {
+            let Some(id) = &profile.password_ref else {
+                continue;
+            };
+            if !used_refs.contains(id) {
+                log::debug!("Deleting password of removed profile {}", profile.name);
+                store.delete(id)?;
+            }
+        }
+
+        let mut on_disk = self.clone();
+        for profile in &mut on_disk.profiles {
+            if let Some((_, password)) = &mut profile.user {
+                password.clear();
+            }
+        }
+        Ok(on_disk)
+    }
+
+    pub fn get_profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
     }
 
     pub fn get_current_profile(&self) -> Option<&Profile> {
         self.current_profile
             .as_ref()
-            .and_then(|name| self.profiles.iter().find(|p| p.name == *name))
+            .and_then(|name| self.get_profile(name))
     }
+}
 
-    /// Used by commands that may change etcd server data.
-    ///
-    /// Return Err if the current profile is locked.
-    pub fn ensure_current_profile_unlocked(&self) -> Result<(), String> {
-        let current_profile = self
-            .get_current_profile()
-            .ok_or_else(|| "No current profile set".to_string())?;
-        if let Some(true) = current_profile.locked {
-            Err("Current profile is locked".to_string())
-        } else {
-            Ok(())
+/// A secret store key for the password of profile `name`, not used by any other profile
+fn unique_ref(name: &str, used: &HashSet<String>) -> String {
+    let base = format!("profile/{name}");
+    let mut id = base.clone();
+    let mut n = 1;
+    while used.contains(&id) {
+        n += 1;
+        id = format!("{base}#{n}");
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("etcd-gui-config-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
         }
+
+        fn config_path(&self) -> PathBuf {
+            self.0.join(AppConfig::CONFIG_FILE_NAME)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Configs are told apart by their current profile
+    fn config(current: &str) -> AppConfig {
+        AppConfig {
+            current_profile: Some(current.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn current_of(path: &Path) -> Option<String> {
+        AppConfig::from_file(path).unwrap().current_profile
+    }
+
+    fn write_backup(path: &Path, timestamp: &str, content: &[u8]) {
+        let dir = AppConfig::backup_dir(path);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("config-{timestamp}.json")), content).unwrap();
+    }
+
+    #[test]
+    fn saves_through_temp_file() {
+        let dir = TempDir::new("save");
+        let path = dir.config_path();
+
+        config("first").save(&path).unwrap();
+        assert_eq!(current_of(&path).as_deref(), Some("first"));
+        assert!(AppConfig::backup_paths(&path).is_empty());
+
+        config("second").save(&path).unwrap();
+        assert_eq!(current_of(&path).as_deref(), Some("second"));
+        let entries: Vec<_> = std::fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert!(!entries.iter().any(|name| name == "config.json.tmp"));
+
+        // The previous config is the one backed up
+        let backups = AppConfig::backup_paths(&path);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(current_of(&backups[0]).as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn keeps_latest_backups() {
+        let dir = TempDir::new("backups");
+        let path = dir.config_path();
+        let valid = serde_json::to_vec(&config("old")).unwrap();
+        for i in 0..CONFIG_BACKUP_COUNT + 2 {
+            write_backup(&path, &format!("20000101T0000{i:02}.000Z"), &valid);
+        }
+
+        config("current").save(&path).unwrap();
+        config("next").save(&path).unwrap();
+
+        let backups = AppConfig::backup_paths(&path);
+        assert_eq!(backups.len(), CONFIG_BACKUP_COUNT);
+        assert_eq!(current_of(&backups[0]).as_deref(), Some("current"));
+        // The oldest ones are removed
+        assert_eq!(
+            backups.last().unwrap().file_name().unwrap(),
+            "config-20000101T000003.000Z.json"
+        );
+    }
+
+    #[test]
+    fn recovers_from_newest_valid_backup() {
+        let dir = TempDir::new("recover");
+        let path = dir.config_path();
+        write_backup(
+            &path,
+            "20000101T000000.000Z",
+            &serde_json::to_vec(&config("older")).unwrap(),
+        );
+        write_backup(
+            &path,
+            "20000101T000001.000Z",
+            &serde_json::to_vec(&config("newer")).unwrap(),
+        );
+        write_backup(&path, "20000101T000002.000Z", b"{");
+        std::fs::write(&path, b"not json").unwrap();
+
+        assert_eq!(current_of(&path).as_deref(), Some("newer"));
+        let corrupt = dir.0.join("config.json.corrupt");
+        assert_eq!(std::fs::read(corrupt).unwrap(), b"not json");
+    }
+
+    #[test]
+    fn defaults_without_valid_backup() {
+        let dir = TempDir::new("default");
+        let path = dir.config_path();
+        std::fs::write(&path, b"{").unwrap();
+
+        assert_eq!(current_of(&path), None);
+        assert!(dir.0.join("config.json.corrupt").exists());
     }
 }
@@ -0,0 +1,147 @@
+use serde_json::Value;
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Upgrades of the config file, `MIGRATIONS[n]` turns schema version `n` into `n + 1`.
+///
+/// Append a migration whenever a change to [`AppConfig`](super::AppConfig) would read
+/// existing files differently, never edit a released one.
+const MIGRATIONS: &[Migration] = &[metrics_path_default];
+
+/// Schema version of the config files written by this build
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+#[derive(Debug)]
+pub enum MigrateError {
+    /// Written by a newer build, which this one must not overwrite with what it understands
+    Newer(u64),
+    Invalid(String),
+}
+
+impl std::fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrateError::Newer(version) => write!(
+                f,
+                "Config schema version {version} is newer than this build supports \
+                 ({SCHEMA_VERSION}), update the app"
+            ),
+            MigrateError::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<MigrateError> for String {
+    fn from(e: MigrateError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Bring a config file of any known schema version up to [`SCHEMA_VERSION`]
+pub fn migrate(config: &mut Value) -> Result<(), MigrateError> {
+    // Files from before versioning have no `schema_version` at all
+    let version = config
+        .get("schema_version")
+        .map(|v| {
+            v.as_u64()
+                .ok_or_else(|| MigrateError::Invalid(format!("Invalid config schema version: {v}")))
+        })
+        .transpose()?
+        .unwrap_or(0);
+    if version > SCHEMA_VERSION as u64 {
+        return Err(MigrateError::Newer(version));
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        log::info!(
+            "Migrating config from schema version {} to {}",
+            from,
+            from + 1
+        );
+        migration(config).map_err(MigrateError::Invalid)?;
+    }
+    if let Some(object) = config.as_object_mut() {
+        object.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    }
+    Ok(())
+}
+
+fn profiles_mut(config: &mut Value) -> impl Iterator<Item = &mut serde_json::Map<String, Value>> {
+    config
+        .get_mut("profiles")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// v0 -> v1: profiles with a missing or `null` `metrics_path` used to get `/metrics`
+/// implicitly, which made it impossible to turn metrics off. Write that default out so
+/// that an unset path can mean no metrics from now on.
+fn metrics_path_default(config: &mut Value) -> Result<(), String> {
+    for profile in profiles_mut(config) {
+        let path = profile.entry("metrics_path").or_insert(Value::Null);
+        if path.is_null() {
+            *path = Value::from("/metrics");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn migrates_unversioned_config() {
+        let mut config = json!({
+            "profiles": [
+                { "name": "default" },
+                { "name": "custom", "metrics_path": "/custom" },
+                { "name": "unset", "metrics_path": null }
+            ]
+        });
+        migrate(&mut config).unwrap();
+
+        assert_eq!(config["schema_version"], json!(SCHEMA_VERSION));
+        let paths: Vec<_> = config["profiles"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|profile| profile["metrics_path"].clone())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![json!("/metrics"), json!("/custom"), json!("/metrics")]
+        );
+    }
+
+    #[test]
+    fn leaves_current_config_alone() {
+        let mut config = json!({ "schema_version": SCHEMA_VERSION, "profiles": [{ "name": "a" }] });
+        let expected = config.clone();
+        migrate(&mut config).unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn rejects_newer_schema() {
+        let newer = SCHEMA_VERSION as u64 + 1;
+        let mut config = json!({ "schema_version": newer, "profiles": [{ "name": "a" }] });
+        let expected = config.clone();
+
+        assert!(matches!(migrate(&mut config), Err(MigrateError::Newer(v)) if v == newer));
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn rejects_invalid_schema_version() {
+        let mut config = json!({ "schema_version": "1" });
+        assert!(matches!(
+            migrate(&mut config),
+            Err(MigrateError::Invalid(_))
+        ));
+    }
+}
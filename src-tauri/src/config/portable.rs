@@ -0,0 +1,416 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{AppConfig, Endpoint, Profile, TlsConfig, migrate};
+
+// Tells profile bundles apart from other JSON files, such as config.json itself
+const BUNDLE_FORMAT: &str = "etcd-gui-profiles";
+const DEFAULT_ETCD_PORT: u16 = 2379;
+
+/// Profiles exported to a file, to be imported on another machine
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProfileBundle {
+    pub format: String,
+    // Schema of the profiles, migrated like config.json on import
+    pub schema_version: u32,
+    pub profiles: Vec<Profile>,
+}
+
+/// What to do with an imported profile whose name is already taken
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum NameCollision {
+    Skip,
+    // Import it as `name (2)`, `name (3)`...
+    #[default]
+    Rename,
+    Overwrite,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ProfileImportResult {
+    // Names the profiles were imported under
+    pub imported: Vec<String>,
+    pub skipped: Vec<String>,
+    pub overwritten: Vec<String>,
+}
+
+/// A profile built from etcdctl settings, for the user to review before saving
+#[derive(Serialize, Debug)]
+pub struct ProfileDraft {
+    pub profile: Profile,
+    // Settings that were ignored or could not be converted
+    pub warnings: Vec<String>,
+}
+
+impl AppConfig {
+    /// Bundle the profiles named `names`, with their passwords only if `include_secrets`
+    pub fn export_profiles(
+        &self,
+        names: &[String],
+        include_secrets: bool,
+    ) -> Result<ProfileBundle, String> {
+        let mut profiles = Vec::with_capacity(names.len());
+        for name in names {
+            let mut profile = self
+                .get_profile(name)
+                .cloned()
+                .ok_or_else(|| format!("Profile {name} not found"))?;
+            // References point into this machine's secret store
+            let password_ref = profile.password_ref.take();
+            if let Some((_, password)) = &mut profile.user {
+                if !include_secrets {
+                    password.clear();
+                } else if password.is_empty() && password_ref.is_some() {
+                    return Err(format!(
+                        "The password of profile {name} is in the locked secret store, unlock it \
+                         or export without secrets"
+                    ));
+                }
+            }
+            profiles.push(profile);
+        }
+
+        Ok(ProfileBundle {
+            format: BUNDLE_FORMAT.to_string(),
+            schema_version: migrate::SCHEMA_VERSION,
+            profiles,
+        })
+    }
+
+    /// Add the profiles of `bundle`, handling names already in use according to `collision`
+    pub fn import_profiles(
+        &mut self,
+        bundle: ProfileBundle,
+        collision: NameCollision,
+    ) -> ProfileImportResult {
+        let mut result = ProfileImportResult::default();
+        for mut profile in bundle.profiles {
+            profile.password_ref = None;
+            let existing = self.profiles.iter().position(|p| p.name == profile.name);
+            match (existing, collision) {
+                (None, _) => (),
+                (Some(_), NameCollision::Skip) => {
+                    result.skipped.push(profile.name);
+                    continue;
+                }
+                (Some(_), NameCollision::Rename) => {
+                    profile.name = self.unused_profile_name(&profile.name);
+                }
+                (Some(index), NameCollision::Overwrite) => {
+                    self.profiles.remove(index);
+                    result.overwritten.push(profile.name.clone());
+                }
+            }
+            result.imported.push(profile.name.clone());
+            self.profiles.push(profile);
+        }
+        result
+    }
+
+    fn unused_profile_name(&self, name: &str) -> String {
+        let taken: HashSet<&str> = self.profiles.iter().map(|p| p.name.as_str()).collect();
+        (2..)
+            .map(|n| format!("{name} ({n})"))
+            .find(|candidate| !taken.contains(candidate.as_str()))
+            .expect("Some profile name should be free")
+    }
+}
+
+/// Read a bundle written by [`AppConfig::export_profiles`], migrating older profiles
+pub fn parse_bundle(content: &[u8]) -> Result<ProfileBundle, String> {
+    let mut value: serde_json::Value =
+        serde_json::from_slice(content).map_err(|e| format!("Invalid profile file: {e}"))?;
+    if value.get("format").and_then(|f| f.as_str()) != Some(BUNDLE_FORMAT) {
+        return Err("Not an etcd-gui profile file".to_string());
+    }
+    // Profiles are stored as in config.json, so the same migrations apply
+    migrate::migrate(&mut value)?;
+    serde_json::from_value(value).map_err(|e| format!("Invalid profile file: {e}"))
+}
+
+/// Parse `KEY=value` lines, as printed by `env` or found in shell scripts
+/// (`export` prefixes, quotes and comments are handled)
+pub fn parse_env_text(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Parse a Go duration as used by etcdctl (`5s`, `500ms`, `1m30s`) into milliseconds
+fn parse_go_duration_ms(value: &str) -> Option<u64> {
+    let mut total = 0f64;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let factor = match &rest[..unit_len] {
+            "ms" => 1.0,
+            "s" => 1000.0,
+            "m" => 60_000.0,
+            "h" => 3_600_000.0,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+        total += number * factor;
+    }
+    Some(total as u64)
+}
+
+/// Split an etcdctl endpoint (`https://host:2379`, `host:2379`, `[::1]:2379`, `host`)
+/// into the scheme-prefixed host and the port
+fn parse_endpoint(endpoint: &str) -> Result<Endpoint, String> {
+    let (scheme, address) = match endpoint.split_once("://") {
+        Some((scheme, address)) => (Some(scheme), address),
+        None => (None, endpoint),
+    };
+    let address = address.trim_end_matches('/');
+    let (host, port) = match address.rsplit_once(':') {
+        // A colon inside brackets belongs to an IPv6 address without a port
+        Some((host, port)) if !port.ends_with(']') => {
+            let port = port
+                .parse()
+                .map_err(|_| format!("Invalid port in endpoint {endpoint}"))?;
+            (host, port)
+        }
+        _ => (address, DEFAULT_ETCD_PORT),
+    };
+    if host.is_empty() {
+        return Err(format!("Invalid endpoint {endpoint}"));
+    }
+    let host = match scheme {
+        Some(scheme) => format!("{scheme}://{host}"),
+        None => host.to_string(),
+    };
+    Ok(Endpoint { host, port })
+}
+
+/// Build a profile from the `ETCDCTL_*` variables of `env`, the way etcdctl reads them
+pub fn profile_from_etcdctl_env(env: &HashMap<String, String>) -> Result<ProfileDraft, String> {
+    let var = |name: &str| {
+        env.get(name)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+    let mut warnings = Vec::new();
+
+    let endpoints = var("ETCDCTL_ENDPOINTS")
+        .ok_or_else(|| "ETCDCTL_ENDPOINTS is not set".to_string())?
+        .split(',')
+        .map(str::trim)
+        .filter(|endpoint| !endpoint.is_empty())
+        .map(parse_endpoint)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let tls = TlsConfig {
+        ca_cert_path: var("ETCDCTL_CACERT").map(str::to_string),
+        client_cert_path: var("ETCDCTL_CERT").map(str::to_string),
+        client_key_path: var("ETCDCTL_KEY").map(str::to_string),
+        server_name: None,
+    };
+    let use_tls = tls.ca_cert_path.is_some()
+        || tls.client_cert_path.is_some()
+        || tls.client_key_path.is_some();
+    // etcdctl switches to TLS when certificates are given, the GUI goes by the URL scheme
+    let scheme = if use_tls { "https" } else { "http" };
+    let endpoints = endpoints
+        .into_iter()
+        .map(|mut endpoint| {
+            if !endpoint.host.contains("://") {
+                endpoint.host = format!("{scheme}://{}", endpoint.host);
+            }
+            endpoint
+        })
+        .collect::<Vec<_>>();
+
+    // `ETCDCTL_USER` is either `name:password` or just `name`
+    let user = var("ETCDCTL_USER").map(|user| match user.split_once(':') {
+        Some((name, password)) => (name.to_string(), password.to_string()),
+        None => (
+            user.to_string(),
+            var("ETCDCTL_PASSWORD").unwrap_or_default().to_string(),
+        ),
+    });
+
+    let mut duration_ms = |name: &str| {
+        let value = var(name)?;
+        let ms = parse_go_duration_ms(value);
+        if ms.is_none() {
+            warnings.push(format!("Ignored {name}: invalid duration {value}"));
+        }
+        ms
+    };
+    let connect_timeout_ms = duration_ms("ETCDCTL_DIAL_TIMEOUT");
+    let timeout_ms = duration_ms("ETCDCTL_COMMAND_TIMEOUT");
+
+    for unsupported in [
+        "ETCDCTL_INSECURE_SKIP_TLS_VERIFY",
+        "ETCDCTL_INSECURE_TRANSPORT",
+        "ETCDCTL_DISCOVERY_SRV",
+    ] {
+        if var(unsupported).is_some() {
+            warnings.push(format!("Ignored {unsupported}: not supported"));
+        }
+    }
+
+    let name = endpoints
+        .first()
+        .map(|endpoint| {
+            let host = endpoint.host.split("://").last().unwrap_or(&endpoint.host);
+            format!("etcdctl {host}")
+        })
+        .ok_or_else(|| "ETCDCTL_ENDPOINTS has no endpoint".to_string())?;
+
+    Ok(ProfileDraft {
+        profile: Profile {
+            name,
+            endpoints,
+            user,
+            password_ref: None,
+            timeout_ms,
+            connect_timeout_ms,
+            locked: None,
+            metrics_path: Some("/metrics".to_string()),
+            tls: use_tls.then_some(tls),
+        },
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(host: &str, port: u16) -> Endpoint {
+        Endpoint {
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    fn parses_env_text() {
+        let env = parse_env_text(
+            "# etcd\n\
+             export ETCDCTL_ENDPOINTS=\"https://a:2379,https://b:2379\"\n\
+             ETCDCTL_USER='root:p=ss'\n\
+             \n\
+             ETCDCTL_DIAL_TIMEOUT = 3s \n\
+             not a variable\n",
+        );
+        assert_eq!(env.len(), 3);
+        assert_eq!(env["ETCDCTL_ENDPOINTS"], "https://a:2379,https://b:2379");
+        assert_eq!(env["ETCDCTL_USER"], "root:p=ss");
+        assert_eq!(env["ETCDCTL_DIAL_TIMEOUT"], "3s");
+    }
+
+    #[test]
+    fn parses_endpoints() {
+        assert_eq!(parse_endpoint("host:2380"), Ok(endpoint("host", 2380)));
+        assert_eq!(
+            parse_endpoint("host"),
+            Ok(endpoint("host", DEFAULT_ETCD_PORT))
+        );
+        assert_eq!(
+            parse_endpoint("https://host:2379/"),
+            Ok(endpoint("https://host", 2379))
+        );
+        assert_eq!(parse_endpoint("[::1]:2380"), Ok(endpoint("[::1]", 2380)));
+        assert_eq!(
+            parse_endpoint("[::1]"),
+            Ok(endpoint("[::1]", DEFAULT_ETCD_PORT))
+        );
+        assert!(parse_endpoint("host:port").is_err());
+        assert!(parse_endpoint("http://:2379").is_err());
+    }
+
+    #[test]
+    fn parses_go_durations() {
+        assert_eq!(parse_go_duration_ms("500ms"), Some(500));
+        assert_eq!(parse_go_duration_ms("5s"), Some(5000));
+        assert_eq!(parse_go_duration_ms("1.5s"), Some(1500));
+        assert_eq!(parse_go_duration_ms("1m30s"), Some(90_000));
+        assert_eq!(parse_go_duration_ms("1h"), Some(3_600_000));
+        assert_eq!(parse_go_duration_ms(""), None);
+        assert_eq!(parse_go_duration_ms("5"), None);
+        assert_eq!(parse_go_duration_ms("5d"), None);
+        assert_eq!(parse_go_duration_ms("s"), None);
+    }
+
+    #[test]
+    fn builds_profile_from_etcdctl_env() {
+        let env = parse_env_text(
+            "ETCDCTL_ENDPOINTS=node1:2379,https://node2:2379\n\
+             ETCDCTL_CACERT=/etc/etcd/ca.pem\n\
+             ETCDCTL_USER=root\n\
+             ETCDCTL_PASSWORD=secret\n\
+             ETCDCTL_COMMAND_TIMEOUT=soon\n\
+             ETCDCTL_INSECURE_TRANSPORT=false\n",
+        );
+        let draft = profile_from_etcdctl_env(&env).unwrap();
+        let profile = draft.profile;
+
+        assert_eq!(profile.name, "etcdctl node1");
+        assert_eq!(
+            profile.endpoints,
+            vec![
+                endpoint("https://node1", 2379),
+                endpoint("https://node2", 2379)
+            ]
+        );
+        assert_eq!(
+            profile.user,
+            Some(("root".to_string(), "secret".to_string()))
+        );
+        assert_eq!(profile.timeout_ms, None);
+        assert_eq!(
+            profile.tls.and_then(|tls| tls.ca_cert_path).as_deref(),
+            Some("/etc/etcd/ca.pem")
+        );
+        assert_eq!(draft.warnings.len(), 2);
+    }
+
+    #[test]
+    fn uses_plain_http_without_certificates() {
+        let env = parse_env_text("ETCDCTL_ENDPOINTS=node1:2379,[::1],https://node2:2379\n");
+        let profile = profile_from_etcdctl_env(&env).unwrap().profile;
+
+        assert_eq!(profile.name, "etcdctl node1");
+        assert_eq!(
+            profile.endpoints,
+            vec![
+                endpoint("http://node1", 2379),
+                endpoint("http://[::1]", DEFAULT_ETCD_PORT),
+                endpoint("https://node2", 2379)
+            ]
+        );
+        assert!(profile.tls.is_none());
+    }
+
+    #[test]
+    fn requires_endpoints_in_etcdctl_env() {
+        assert!(profile_from_etcdctl_env(&HashMap::new()).is_err());
+    }
+}
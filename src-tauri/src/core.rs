@@ -1,25 +1,64 @@
+mod auth;
+mod concurrency;
+mod diff;
+mod import;
+mod maintenance;
 mod split_batch;
+mod sync;
+mod txn;
 
-use etcd_client::{Client, Error, GetOptions, SortOrder, SortTarget};
+pub use auth::{
+    EffectivePermission, PermissionRequest, RoleInfo, UserInfo, add_role, add_user,
+    change_password, check_permission, delete_role, delete_user, disable_auth, enable_auth,
+    grant_permission, grant_role, list_roles, list_users, revoke_permission, revoke_role,
+};
+pub use concurrency::{
+    Contender, Contenders, ForceReleaseResult, force_release, list_contenders, observe_election,
+};
+pub use diff::{DiffResult, diff_prefixes};
+pub use import::{ConflictPolicy, ImportProgress, import_entries, rewrite_prefix};
+pub use maintenance::{
+    HashKvResult, MaintenanceResult, SnapshotProgress, SnapshotResult, compact, defragment_member,
+    hash_kv_all, move_leader, snapshot_member,
+};
+pub use sync::{SyncOptions, SyncResult, sync_prefix};
+pub use txn::{TxnRequest, TxnResult};
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use etcd_client::{
+    AlarmAction, AlarmOptions, AlarmType, Client, Compare, CompareOp, DeleteOptions, Error,
+    GetOptions, KeyValue, LeaseGrantOptions, LeaseTimeToLiveOptions, MemberAddOptions, PutOptions,
+    SortOrder, SortTarget, Txn, TxnOp, TxnOpResponse,
+};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
 
-use crate::client::{Item, should_refresh};
+use crate::client::{Item, ItemKey, connect_endpoints, should_refresh};
+use crate::config::Profile;
 use crate::core::split_batch::{
-    KeysOnlySplitter, KvSplitter, ValuesInRangeSplitter, execute_splittable, is_out_of_range_error,
+    KeysOnlySplitter, KvSplitter, RawKvSplitter, ValuesInRangeSplitter, execute_splittable,
+    for_each_batch, is_compacted_error, is_out_of_range_error,
 };
-use crate::state::AppState;
+use crate::dump::{DumpFormat, DumpHeader, DumpWriter};
+use crate::lease::LeaseInfo;
+use crate::state::Session;
+use crate::watch::WatchRequest;
 
-async fn perform_op<T, F, Fut>(state: &mut AppState, f: F) -> Result<T, String>
+async fn perform_op<T, F, Fut>(session: &Session, f: F) -> Result<T, String>
 where
     F: Fn(Client) -> Fut,
     Fut: std::future::Future<Output = Result<T, Error>>,
 {
-    let client = state.get_client().await?.clone();
+    let (client, generation) = session.client().await?;
     let res = f(client).await;
 
     if should_refresh(&res) {
         log::warn!("Refreshing client connection...");
-        state.etcd_client = None;
-        let client = state.get_client().await?.clone();
+        let (client, _) = session.refresh_client(generation).await?;
         f(client).await.map_err(|e| e.to_string())
     } else {
         res.map_err(|e| e.to_string())
@@ -27,8 +66,8 @@ where
 }
 
 /// Fetch all keys with the specified prefix
-pub async fn list_items(prefix: &str, state: &mut AppState) -> Result<Vec<Item>, String> {
-    perform_op(state, |mut client| async move {
+pub async fn list_items(prefix: &str, session: &Session) -> Result<Vec<Item>, String> {
+    perform_op(session, |mut client| async move {
         let range_end = range_end_of_prefix(prefix.as_bytes());
         let (sort_target, sort_order) = (SortTarget::Key, SortOrder::Ascend);
         let opt = GetOptions::new()
@@ -37,29 +76,8 @@ pub async fn list_items(prefix: &str, state: &mut AppState) -> Result<Vec<Item>,
             .with_sort(sort_target, sort_order);
         let res = client.get(prefix, Some(opt)).await;
         if !split_batch::is_out_of_range_error(&res) {
-            return res.map(|mut response| {
-                response
-                    .take_kvs()
-                    .into_iter()
-                    .filter_map(|kv| {
-                        if let (Ok(key_str), Ok(value_str)) = (
-                            std::str::from_utf8(kv.key()),
-                            std::str::from_utf8(kv.value()),
-                        ) {
-                            Some(Item {
-                                key: key_str.to_owned(),
-                                value: value_str.to_owned(),
-                                version: kv.version(),
-                                create_revision: kv.create_revision(),
-                                mod_revision: kv.mod_revision(),
-                                lease: kv.lease(),
-                            })
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            });
+            return res
+                .map(|mut response| response.take_kvs().into_iter().map(Item::from).collect());
         }
 
         log::warn!("Received out-of-range error, retrying...");
@@ -90,8 +108,8 @@ fn range_end_of_prefix(prefix_key: &[u8]) -> Vec<u8> {
 }
 
 /// Fetch only keys with the specified prefix
-pub async fn list_keys_only(prefix: &str, state: &mut AppState) -> Result<Vec<String>, String> {
-    perform_op(state, |mut client| async move {
+pub async fn list_keys_only(prefix: &str, session: &Session) -> Result<Vec<ItemKey>, String> {
+    perform_op(session, |mut client| async move {
         let range_end = range_end_of_prefix(prefix.as_bytes());
         let (sort_target, sort_order) = (SortTarget::Key, SortOrder::Ascend);
         let opt = GetOptions::new()
@@ -101,13 +119,8 @@ pub async fn list_keys_only(prefix: &str, state: &mut AppState) -> Result<Vec<St
             .with_sort(sort_target, sort_order);
         let res = client.get(prefix, Some(opt)).await;
         if !is_out_of_range_error(&res) {
-            return res.map(|mut response| {
-                response
-                    .take_kvs()
-                    .iter()
-                    .filter_map(|kv| std::str::from_utf8(kv.key()).ok().map(str::to_owned))
-                    .collect()
-            });
+            return res
+                .map(|mut response| response.take_kvs().into_iter().map(ItemKey::from).collect());
         }
         log::warn!("Received out-of-range error, retrying...");
 
@@ -123,20 +136,20 @@ pub async fn list_keys_only(prefix: &str, state: &mut AppState) -> Result<Vec<St
     .await
 }
 
-fn make_exclusive_end_from_inclusive(end_inclusive: &str) -> Vec<u8> {
+fn make_exclusive_end_from_inclusive(end_inclusive: &[u8]) -> Vec<u8> {
     // Append a NUL byte to create an exclusive end that includes the original last key
-    let mut end = end_inclusive.as_bytes().to_vec();
+    let mut end = end_inclusive.to_vec();
     end.push(0);
     end
 }
 
 /// Fetch values in a key range [start_key, end_key] inclusive, sorted by key
 pub async fn get_values_in_range(
-    start_key: &str,
-    end_inclusive: &str,
-    state: &mut AppState,
+    start_key: &[u8],
+    end_inclusive: &[u8],
+    session: &Session,
 ) -> Result<Vec<Item>, String> {
-    perform_op(state, |mut client| async move {
+    perform_op(session, |mut client| async move {
         let end_exclusive = make_exclusive_end_from_inclusive(end_inclusive);
         let (sort_target, sort_order) = (SortTarget::Key, SortOrder::Ascend);
         let opt = GetOptions::new()
@@ -145,28 +158,8 @@ pub async fn get_values_in_range(
             .with_sort(sort_target, sort_order);
         let res = client.get(start_key, Some(opt)).await;
         if !is_out_of_range_error(&res) {
-            return res.map(|response| {
-                response
-                    .kvs()
-                    .iter()
-                    .filter_map(|kv| {
-                        match (
-                            std::str::from_utf8(kv.key()),
-                            std::str::from_utf8(kv.value()),
-                        ) {
-                            (Ok(key_str), Ok(value_str)) => Some(Item {
-                                key: key_str.to_owned(),
-                                value: value_str.to_owned(),
-                                version: kv.version(),
-                                create_revision: kv.create_revision(),
-                                mod_revision: kv.mod_revision(),
-                                lease: kv.lease(),
-                            }),
-                            _ => None,
-                        }
-                    })
-                    .collect()
-            });
+            return res
+                .map(|mut response| response.take_kvs().into_iter().map(Item::from).collect());
         }
         log::warn!("Received out-of-range error, retrying...");
 
@@ -182,25 +175,210 @@ pub async fn get_values_in_range(
     .await
 }
 
-/// Add a new key-value pair to etcd
-pub async fn put_key(key: &str, value: &str, state: &mut AppState) -> Result<(), String> {
-    perform_op(state, |mut client| async move {
-        client.put(key, value, None).await.map(|_| ())
+/// Error returned by writes that are conditional on the state of a key.
+///
+/// Serialized as a plain string unless it is a [`Conflict`].
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum WriteError {
+    Conflict(Conflict),
+    Message(String),
+}
+
+/// The key was modified since the caller loaded it
+#[derive(Serialize, Debug)]
+pub struct Conflict {
+    pub expected_mod_revision: i64,
+    // None if the key has been deleted
+    pub current: Option<Item>,
+}
+
+impl From<String> for WriteError {
+    fn from(message: String) -> Self {
+        WriteError::Message(message)
+    }
+}
+
+/// Add a new key-value pair to etcd, optionally attached to a lease
+///
+/// With `expected_mod_revision`, the put only happens if the key is still at that
+/// revision (0 means the key must not exist), otherwise a [`Conflict`] holding the
+/// current item is returned.
+pub async fn put_key(
+    key: &[u8],
+    value: &[u8],
+    lease: Option<i64>,
+    expected_mod_revision: Option<i64>,
+    session: &Session,
+) -> Result<(), WriteError> {
+    let Some(expected_mod_revision) = expected_mod_revision else {
+        return perform_op(session, |mut client| async move {
+            let options = lease.map(|id| PutOptions::new().with_lease(id));
+            client.put(key, value, options).await.map(|_| ())
+        })
+        .await
+        .map_err(WriteError::from);
+    };
+
+    let response = perform_op(session, |mut client| async move {
+        let options = lease.map(|id| PutOptions::new().with_lease(id));
+        let txn = Txn::new()
+            .when([Compare::mod_revision(
+                key,
+                CompareOp::Equal,
+                expected_mod_revision,
+            )])
+            .and_then([TxnOp::put(key, value, options)])
+            .or_else([TxnOp::get(key, None)]);
+        client.txn(txn).await
+    })
+    .await?;
+
+    if response.succeeded() {
+        return Ok(());
+    }
+
+    let current = response
+        .op_responses()
+        .into_iter()
+        .find_map(|op| match op {
+            TxnOpResponse::Get(mut get) => get.take_kvs().into_iter().next(),
+            _ => None,
+        })
+        .map(Item::from);
+    Err(WriteError::Conflict(Conflict {
+        expected_mod_revision,
+        current,
+    }))
+}
+
+/// Run a multi-op transaction, the response holds one result per executed op
+pub async fn execute_txn(request: &TxnRequest, session: &Session) -> Result<TxnResult, String> {
+    let txn = request.build()?;
+    perform_op(session, |mut client| {
+        let txn = txn.clone();
+        async move { client.txn(txn).await.map(TxnResult::from) }
     })
     .await
 }
 
 /// Delete a key from etcd
-pub async fn delete_key(key: &str, state: &mut AppState) -> Result<(), String> {
-    perform_op(state, |mut client| async move {
+pub async fn delete_key(key: &[u8], session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
         client.delete(key, None).await.map(|_| ())
     })
     .await
 }
 
+/// Keys removed (or that would be removed) by a range delete
+#[derive(Serialize, Debug)]
+pub struct DeleteRangeResult {
+    pub dry_run: bool,
+    pub count: i64,
+    // First keys of the range, only filled on dry runs
+    pub sample: Vec<ItemKey>,
+    // Deleted entries, only filled when requested so they can be restored
+    pub prev_kvs: Vec<Item>,
+}
+
+/// Delete every key with the specified prefix
+pub async fn delete_prefix(
+    prefix: &[u8],
+    dry_run: bool,
+    prev_kv: bool,
+    sample_size: i64,
+    session: &Session,
+) -> Result<DeleteRangeResult, String> {
+    if prefix.is_empty() {
+        return Err("Refusing to delete with an empty prefix".to_string());
+    }
+    let range_end = range_end_of_prefix(prefix);
+    delete_range(prefix, &range_end, dry_run, prev_kv, sample_size, session).await
+}
+
+/// Delete every key in [start_key, end_key] inclusive
+pub async fn delete_range_inclusive(
+    start_key: &[u8],
+    end_inclusive: &[u8],
+    dry_run: bool,
+    prev_kv: bool,
+    sample_size: i64,
+    session: &Session,
+) -> Result<DeleteRangeResult, String> {
+    let range_end = make_exclusive_end_from_inclusive(end_inclusive);
+    delete_range(
+        start_key,
+        &range_end,
+        dry_run,
+        prev_kv,
+        sample_size,
+        session,
+    )
+    .await
+}
+
+// Keys listed by a dry-run delete, the count covers the whole range anyway
+const MAX_DELETE_SAMPLE_SIZE: i64 = 1000;
+
+async fn delete_range(
+    start_key: &[u8],
+    range_end: &[u8],
+    dry_run: bool,
+    prev_kv: bool,
+    sample_size: i64,
+    session: &Session,
+) -> Result<DeleteRangeResult, String> {
+    if dry_run {
+        // A limit of 0 or less is no limit at all for etcd
+        let sample_size = sample_size.clamp(1, MAX_DELETE_SAMPLE_SIZE);
+        return perform_op(session, |mut client| async move {
+            let mut response = client
+                .get(
+                    start_key,
+                    Some(
+                        GetOptions::new()
+                            .with_range(range_end)
+                            .with_keys_only()
+                            .with_limit(sample_size)
+                            .with_sort(SortTarget::Key, SortOrder::Ascend),
+                    ),
+                )
+                .await?;
+            Ok(DeleteRangeResult {
+                dry_run,
+                // `count` ignores the limit and reports the whole range
+                count: response.count(),
+                sample: response.take_kvs().into_iter().map(ItemKey::from).collect(),
+                prev_kvs: Vec::new(),
+            })
+        })
+        .await;
+    }
+
+    perform_op(session, |mut client| async move {
+        let mut options = DeleteOptions::new().with_range(range_end);
+        if prev_kv {
+            options = options.with_prev_key();
+        }
+        let response = client.delete(start_key, Some(options)).await?;
+        Ok(DeleteRangeResult {
+            dry_run,
+            count: response.deleted(),
+            sample: Vec::new(),
+            prev_kvs: response
+                .prev_kvs()
+                .iter()
+                .cloned()
+                .map(Item::from)
+                .collect(),
+        })
+    })
+    .await
+}
+
 /// Get cluster member list
-pub async fn get_cluster_members(state: &mut AppState) -> Result<Vec<etcd_client::Member>, String> {
-    perform_op(state, |mut client| async move {
+pub async fn get_cluster_members(session: &Session) -> Result<Vec<etcd_client::Member>, String> {
+    perform_op(session, |mut client| async move {
         client
             .member_list()
             .await
@@ -209,41 +387,509 @@ pub async fn get_cluster_members(state: &mut AppState) -> Result<Vec<etcd_client
     .await
 }
 
+/// Add a member with the given peer URLs, optionally as a non-voting learner
+pub async fn add_member(
+    peer_urls: &[String],
+    is_learner: bool,
+    session: &Session,
+) -> Result<etcd_client::Member, String> {
+    perform_op(session, |mut client| async move {
+        let options = is_learner.then(|| MemberAddOptions::new().with_is_learner());
+        let response = client.member_add(peer_urls.to_vec(), options).await?;
+        response
+            .member()
+            .cloned()
+            .ok_or_else(|| Error::InvalidArgs("Member add returned no member".to_string()))
+    })
+    .await
+}
+
+/// Remove a member from the cluster
+pub async fn remove_member(id: u64, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.member_remove(id).await.map(|_| ())
+    })
+    .await
+}
+
+/// Replace the peer URLs of a member
+pub async fn update_member(id: u64, peer_urls: &[String], session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client
+            .member_update(id, peer_urls.to_vec())
+            .await
+            .map(|_| ())
+    })
+    .await
+}
+
+/// Promote a learner to a voting member, fails until the learner has caught up
+pub async fn promote_member(id: u64, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.member_promote(id).await.map(|_| ())
+    })
+    .await
+}
+
 /// Get cluster status for a specific endpoint
-pub async fn get_cluster_status(
-    state: &mut AppState,
-) -> Result<etcd_client::StatusResponse, String> {
-    perform_op(state, |mut client| async move { client.status().await }).await
+pub async fn get_cluster_status(session: &Session) -> Result<etcd_client::StatusResponse, String> {
+    perform_op(session, |mut client| async move { client.status().await }).await
+}
+
+/// Alarm raised by a member, writes are refused until it is disarmed
+#[derive(Serialize, Debug)]
+pub struct AlarmInfo {
+    pub member_id: u64,
+    pub hex_id: String,
+    pub alarm: AlarmKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AlarmKind {
+    // The member's backend exceeded its space quota
+    Nospace,
+    // The member's data is inconsistent with the rest of the cluster
+    Corrupt,
+}
+
+impl From<AlarmKind> for AlarmType {
+    fn from(kind: AlarmKind) -> Self {
+        match kind {
+            AlarmKind::Nospace => AlarmType::Nospace,
+            AlarmKind::Corrupt => AlarmType::Corrupt,
+        }
+    }
+}
+
+fn alarm_infos(response: etcd_client::AlarmResponse) -> Vec<AlarmInfo> {
+    response
+        .alarms()
+        .iter()
+        .filter_map(|alarm| {
+            let kind = match alarm.alarm() {
+                AlarmType::Nospace => AlarmKind::Nospace,
+                AlarmType::Corrupt => AlarmKind::Corrupt,
+                AlarmType::None => return None,
+            };
+            Some(AlarmInfo {
+                member_id: alarm.member_id(),
+                hex_id: format!("{:x}", alarm.member_id()),
+                alarm: kind,
+            })
+        })
+        .collect()
+}
+
+/// List the alarms active on any member
+pub async fn list_alarms(session: &Session) -> Result<Vec<AlarmInfo>, String> {
+    perform_op(session, |mut client| async move {
+        client
+            .alarm(AlarmAction::Get, AlarmType::None, None)
+            .await
+            .map(alarm_infos)
+    })
+    .await
+}
+
+/// Disarm an alarm of a member, returns the alarms still active
+pub async fn disarm_alarm(
+    member_id: u64,
+    alarm: AlarmKind,
+    session: &Session,
+) -> Result<Vec<AlarmInfo>, String> {
+    perform_op(session, |mut client| async move {
+        let options = AlarmOptions::new().with_member(member_id);
+        client
+            .alarm(AlarmAction::Deactivate, alarm.into(), Some(options))
+            .await?;
+        client
+            .alarm(AlarmAction::Get, AlarmType::None, None)
+            .await
+            .map(alarm_infos)
+    })
+    .await
+}
+
+// Upper bound on reaching a single member, so one node that is down does not stall
+// the whole status overview
+const MEMBER_STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Status of a single member, reported by the member itself
+#[derive(Serialize, Debug)]
+pub struct MemberStatus {
+    pub member_id: u64,
+    pub hex_id: String,
+    // Client URL the status was read from
+    pub endpoint: Option<String>,
+    // Missing if no client URL of the member could be reached, see `error`
+    pub status: Option<EndpointStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EndpointStatus {
+    pub version: String,
+    pub db_size: i64,
+    pub db_size_in_use: i64,
+    pub leader: u64,
+    pub raft_index: u64,
+    pub raft_term: u64,
+    pub raft_applied_index: u64,
+    pub is_learner: bool,
+    // Alarms and other errors reported by the member
+    pub errors: Vec<String>,
+}
+
+impl From<etcd_client::StatusResponse> for EndpointStatus {
+    fn from(status: etcd_client::StatusResponse) -> Self {
+        EndpointStatus {
+            version: status.version().to_string(),
+            db_size: status.db_size(),
+            db_size_in_use: status.db_size_in_use(),
+            leader: status.leader(),
+            raft_index: status.raft_index(),
+            raft_term: status.raft_term(),
+            raft_applied_index: status.raft_applied_index(),
+            is_learner: status.is_learner(),
+            errors: status.errors().to_vec(),
+        }
+    }
+}
+
+/// Ask every member for its own status, connecting to each of them separately
+pub async fn get_member_statuses(
+    profile: &Profile,
+    members: &[etcd_client::Member],
+) -> Vec<MemberStatus> {
+    let mut tasks = JoinSet::new();
+    for (index, member) in members.iter().enumerate() {
+        let (profile, member) = (profile.clone(), member.clone());
+        tasks.spawn(async move { (index, get_member_status(&profile, &member).await) });
+    }
+
+    let mut statuses: Vec<_> = tasks.join_all().await;
+    statuses.sort_by_key(|(index, _)| *index);
+    statuses.into_iter().map(|(_, status)| status).collect()
+}
+
+async fn get_member_status(profile: &Profile, member: &etcd_client::Member) -> MemberStatus {
+    let mut result = MemberStatus {
+        member_id: member.id(),
+        hex_id: format!("{:x}", member.id()),
+        endpoint: None,
+        status: None,
+        error: None,
+    };
+    if member.client_urls().is_empty() {
+        // Learners that have not started yet have no client URL
+        result.error = Some("Member has no client URL".to_string());
+        return result;
+    }
+
+    for url in member.client_urls() {
+        let status = tokio::time::timeout(MEMBER_STATUS_TIMEOUT, async {
+            let mut client = connect_endpoints(profile, vec![url.clone()]).await?;
+            client.status().await.map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap_or_else(|_| Err("Timed out".to_string()));
+
+        result.endpoint = Some(url.clone());
+        match status {
+            Ok(status) => {
+                result.status = Some(EndpointStatus::from(status));
+                result.error = None;
+                break;
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to get status of member {:x} at {}: {}",
+                    member.id(),
+                    url,
+                    e
+                );
+                result.error = Some(e);
+            }
+        }
+    }
+    result
 }
 
 /// Get a key's value at a specific revision
 pub async fn get_key_at_revision(
-    key: &str,
+    key: &[u8],
     revision: i64,
-    state: &mut AppState,
+    session: &Session,
 ) -> Result<Option<Item>, String> {
-    perform_op(state, |mut client| async move {
+    perform_op(session, |mut client| async move {
         client
             .get(key, Some(GetOptions::new().with_revision(revision)))
             .await
-            .map(|mut response| {
-                if let Some(kv) = response.take_kvs().into_iter().next() {
-                    if let (Ok(key_str), Ok(value_str)) = (
-                        std::str::from_utf8(kv.key()),
-                        std::str::from_utf8(kv.value()),
-                    ) {
-                        return Some(Item {
-                            key: key_str.to_owned(),
-                            value: value_str.to_owned(),
-                            version: kv.version(),
-                            create_revision: kv.create_revision(),
-                            mod_revision: kv.mod_revision(),
-                            lease: kv.lease(),
-                        });
-                    }
+            .map(|mut response| response.take_kvs().into_iter().next().map(Item::from))
+    })
+    .await
+}
+
+/// Open a watch stream on a key or prefix
+pub async fn watch(
+    key: &[u8],
+    request: &WatchRequest,
+    session: &Session,
+) -> Result<(etcd_client::Watcher, etcd_client::WatchStream), String> {
+    perform_op(session, |mut client| async move {
+        client.watch(key, Some(request.options())).await
+    })
+    .await
+}
+
+/// List the IDs of all leases
+pub async fn list_leases(session: &Session) -> Result<Vec<i64>, String> {
+    perform_op(session, |mut client| async move {
+        client
+            .leases()
+            .await
+            .map(|response| response.leases().iter().map(|l| l.id()).collect())
+    })
+    .await
+}
+
+/// Get a lease's TTL and the keys attached to it
+pub async fn get_lease_info(id: i64, session: &Session) -> Result<LeaseInfo, String> {
+    perform_op(session, |mut client| async move {
+        client
+            .lease_time_to_live(id, Some(LeaseTimeToLiveOptions::new().with_keys()))
+            .await
+            .map(LeaseInfo::from)
+    })
+    .await
+}
+
+/// Grant a lease with the given TTL in seconds, returns the lease ID
+///
+/// The server picks the ID when `id` is `None`.
+pub async fn grant_lease(ttl: i64, id: Option<i64>, session: &Session) -> Result<i64, String> {
+    perform_op(session, |mut client| async move {
+        let options = id.map(|id| LeaseGrantOptions::new().with_id(id));
+        client.lease_grant(ttl, options).await.map(|r| r.id())
+    })
+    .await
+}
+
+/// Revoke a lease, deleting every key attached to it
+pub async fn revoke_lease(id: i64, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.lease_revoke(id).await.map(|_| ())
+    })
+    .await
+}
+
+/// Open a keep-alive stream for a lease
+pub async fn lease_keep_alive(
+    id: i64,
+    session: &Session,
+) -> Result<(etcd_client::LeaseKeeper, etcd_client::LeaseKeepAliveStream), String> {
+    perform_op(session, |mut client| async move {
+        client.lease_keep_alive(id).await
+    })
+    .await
+}
+
+/// Refresh a lease once, returns its new TTL
+pub async fn keep_alive_lease_once(id: i64, session: &Session) -> Result<i64, String> {
+    perform_op(session, |mut client| async move {
+        let (mut keeper, mut stream) = client.lease_keep_alive(id).await?;
+        keeper.keep_alive().await?;
+        Ok(stream.message().await?.map_or(0, |r| r.ttl()))
+    })
+    .await
+}
+
+/// Versions of a key, newest first
+#[derive(Serialize, Debug, Default)]
+pub struct KeyHistory {
+    pub versions: Vec<Item>,
+    // Older versions exist but have been compacted away
+    pub compacted: bool,
+    // Stopped early because the requested limit was reached
+    pub truncated: bool,
+}
+
+/// Walk a key's history backwards, one `mod_revision` at a time.
+///
+/// Starts at `from_revision` (latest if `None`) and stops at the key's creation or at
+/// the compaction point, whichever comes first.
+pub async fn get_key_history(
+    key: &[u8],
+    from_revision: Option<i64>,
+    limit: Option<usize>,
+    session: &Session,
+) -> Result<KeyHistory, String> {
+    perform_op(session, |mut client| async move {
+        let mut history = KeyHistory::default();
+        // Revision 0 reads the latest value
+        let mut revision = from_revision.unwrap_or(0);
+
+        loop {
+            if limit.is_some_and(|limit| history.versions.len() >= limit) {
+                history.truncated = true;
+                break;
+            }
+
+            let kv = match client
+                .get(key, Some(GetOptions::new().with_revision(revision)))
+                .await
+            {
+                Ok(mut response) => response.take_kvs().into_iter().next(),
+                Err(e) if is_compacted_error(&e) => {
+                    log::debug!("Reached compaction point at revision {}", revision);
+                    history.compacted = true;
+                    break;
                 }
-                None
-            })
+                Err(e) => return Err(e),
+            };
+
+            // Missing means the key did not exist yet (or was deleted) at this revision
+            let Some(kv) = kv else { break };
+            revision = kv.mod_revision() - 1;
+            history.versions.push(Item::from(kv));
+            if revision <= 0 {
+                break;
+            }
+        }
+
+        Ok(history)
     })
     .await
 }
+
+/// Summary of a finished export
+#[derive(Serialize, Debug)]
+pub struct ExportResult {
+    pub path: String,
+    pub count: u64,
+    // Revision the whole dump was read at
+    pub revision: i64,
+}
+
+/// Export every key with the specified prefix to `path`
+pub async fn export_prefix(
+    prefix: &[u8],
+    path: &Path,
+    format: DumpFormat,
+    session: &Session,
+) -> Result<ExportResult, String> {
+    let range_end = range_end_of_prefix(prefix);
+    let source = format!("prefix '{}'", String::from_utf8_lossy(prefix));
+    export_range(prefix, &range_end, &source, path, format, session).await
+}
+
+/// Export every key in [start_key, end_key] inclusive to `path`
+pub async fn export_range_inclusive(
+    start_key: &[u8],
+    end_inclusive: &[u8],
+    path: &Path,
+    format: DumpFormat,
+    session: &Session,
+) -> Result<ExportResult, String> {
+    let range_end = make_exclusive_end_from_inclusive(end_inclusive);
+    let source = format!(
+        "range '{}' to '{}'",
+        String::from_utf8_lossy(start_key),
+        String::from_utf8_lossy(end_inclusive)
+    );
+    export_range(start_key, &range_end, &source, path, format, session).await
+}
+
+// Batches fetched ahead of the dump writer
+const EXPORT_QUEUE_BATCHES: usize = 2;
+
+async fn export_range(
+    start_key: &[u8],
+    range_end: &[u8],
+    source: &str,
+    path: &Path,
+    format: DumpFormat,
+    session: &Session,
+) -> Result<ExportResult, String> {
+    // Write next to the target and rename at the end, so a failed export never
+    // leaves a truncated dump behind
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    let part_path = PathBuf::from(part_path);
+    let part = part_path.as_path();
+
+    let res = perform_op(session, |mut client| async move {
+        // Pin the revision so batches fetched later still form a consistent snapshot
+        let response = client
+            .get(
+                start_key,
+                Some(GetOptions::new().with_range(range_end).with_count_only()),
+            )
+            .await?;
+        let header = response.header().map(DumpHeader::from).unwrap_or_default();
+        let revision = header.revision;
+
+        // File writes block, so they run on their own thread while batches are fetched.
+        // The queue is bounded so that fetching waits for a slow disk instead of
+        // piling the whole range up in memory
+        let (batches, mut received) =
+            tokio::sync::mpsc::channel::<Vec<KeyValue>>(EXPORT_QUEUE_BATCHES);
+        let (part, source) = (part.to_owned(), source.to_owned());
+        let writing = tokio::task::spawn_blocking(move || {
+            let file = BufWriter::new(File::create(&part)?);
+            let mut writer = DumpWriter::begin(format, file, &header, &source)?;
+            while let Some(batch) = received.blocking_recv() {
+                batch.iter().try_for_each(|kv| writer.write(kv))?;
+            }
+            writer.finish()
+        });
+        let fetched = for_each_batch(
+            &mut client,
+            RawKvSplitter { revision },
+            (start_key, range_end),
+            (SortTarget::Key, SortOrder::Ascend),
+            |batch| {
+                let batches = batches.clone();
+                async move {
+                    batches
+                        .send(batch)
+                        .await
+                        .map_err(|_| std::io::Error::other("The dump writer stopped"))
+                }
+            },
+        )
+        .await;
+        drop(batches);
+
+        // A write error explains why sending batches failed, report it first
+        let count = writing.await.map_err(std::io::Error::other)??;
+        fetched?;
+        Ok((count, revision))
+    })
+    .await;
+
+    let (count, revision) = match res {
+        Ok(res) => res,
+        Err(e) => {
+            if let Err(err) = tokio::fs::remove_file(part).await {
+                log::debug!("Failed to remove {}: {err}", part.display());
+            }
+            return Err(e);
+        }
+    };
+    tokio::fs::rename(part, path)
+        .await
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    log::info!(
+        "Exported {count} keys at revision {revision} to {}",
+        path.display()
+    );
+
+    Ok(ExportResult {
+        path: path.display().to_string(),
+        count,
+        revision,
+    })
+}
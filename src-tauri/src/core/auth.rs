@@ -0,0 +1,383 @@
+use etcd_client::{Permission, PermissionType, RoleRevokePermissionOptions, UserAddOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::client::Encoding;
+use crate::core::{perform_op, range_end_of_prefix};
+use crate::state::Session;
+
+const ROOT: &str = "root";
+
+#[derive(Serialize, Debug)]
+pub struct UserInfo {
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RoleInfo {
+    pub name: String,
+    pub permissions: Vec<PermissionInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionKind {
+    Read,
+    Write,
+    Readwrite,
+}
+
+/// A permission granted to a role, on a single key or a range of keys
+#[derive(Serialize, Debug)]
+pub struct PermissionInfo {
+    pub perm_type: PermissionKind,
+    pub key: String,
+    pub key_encoding: Encoding,
+    // Empty for a single key
+    pub range_end: String,
+    pub range_end_encoding: Encoding,
+    // The range covers exactly the keys starting with `key`
+    pub prefix: bool,
+}
+
+impl From<&Permission> for PermissionInfo {
+    fn from(permission: &Permission) -> Self {
+        let perm_type = match PermissionType::try_from(permission.get_type()) {
+            Ok(PermissionType::Write) => PermissionKind::Write,
+            Ok(PermissionType::Readwrite) => PermissionKind::Readwrite,
+            _ => PermissionKind::Read,
+        };
+        let prefix = permission.is_prefix();
+        let (key, key_encoding) = Encoding::encode_auto(permission.key().to_vec());
+        let (range_end, range_end_encoding) =
+            Encoding::encode_auto(permission.range_end().to_vec());
+        PermissionInfo {
+            perm_type,
+            key,
+            key_encoding,
+            range_end,
+            range_end_encoding,
+            prefix,
+        }
+    }
+}
+
+/// A permission to grant or revoke, as described by the UI
+#[derive(Deserialize, Debug)]
+pub struct PermissionRequest {
+    pub perm_type: PermissionKind,
+    pub key: String,
+    #[serde(default)]
+    pub key_encoding: Encoding,
+    // Cover every key starting with `key`
+    #[serde(default)]
+    pub prefix: bool,
+    // Exclusive end of the range, ignored with `prefix`
+    pub range_end: Option<String>,
+    #[serde(default)]
+    pub range_end_encoding: Encoding,
+}
+
+impl PermissionRequest {
+    /// Decoded key and range end, the latter empty for a single key
+    fn key_range(&self) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let key = self.key_encoding.decode(&self.key)?;
+        let range_end = if self.prefix {
+            range_end_of_prefix(&key)
+        } else if let Some(range_end) = &self.range_end {
+            self.range_end_encoding.decode(range_end)?
+        } else {
+            Vec::new()
+        };
+        Ok((key, range_end))
+    }
+}
+
+/// List every user along with their roles
+pub async fn list_users(session: &Session) -> Result<Vec<UserInfo>, String> {
+    perform_op(session, |mut client| async move {
+        let names = client.user_list().await?.users().to_vec();
+        let mut users = Vec::with_capacity(names.len());
+        for name in names {
+            let roles = client.user_get(name.as_str()).await?.roles().to_vec();
+            users.push(UserInfo { name, roles });
+        }
+        Ok(users)
+    })
+    .await
+}
+
+/// Add a user, without a password it can only authenticate with a client certificate
+pub async fn add_user(name: &str, password: Option<&str>, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        let res = match password {
+            Some(password) => client.user_add(name, password, None).await,
+            None => {
+                let options = UserAddOptions::new().with_no_pwd();
+                client.user_add(name, "", Some(options)).await
+            }
+        };
+        res.map(|_| ())
+    })
+    .await
+}
+
+pub async fn delete_user(name: &str, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.user_delete(name).await.map(|_| ())
+    })
+    .await
+}
+
+pub async fn change_password(name: &str, password: &str, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client
+            .user_change_password(name, password)
+            .await
+            .map(|_| ())
+    })
+    .await
+}
+
+pub async fn grant_role(user: &str, role: &str, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.user_grant_role(user, role).await.map(|_| ())
+    })
+    .await
+}
+
+pub async fn revoke_role(user: &str, role: &str, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.user_revoke_role(user, role).await.map(|_| ())
+    })
+    .await
+}
+
+/// List every role along with its permissions
+pub async fn list_roles(session: &Session) -> Result<Vec<RoleInfo>, String> {
+    perform_op(session, |mut client| async move {
+        let names = client.role_list().await?.roles().to_vec();
+        let mut roles = Vec::with_capacity(names.len());
+        for name in names {
+            let response = client.role_get(name.as_str()).await?;
+            let permissions = response
+                .permissions()
+                .iter()
+                .map(PermissionInfo::from)
+                .collect();
+            roles.push(RoleInfo { name, permissions });
+        }
+        Ok(roles)
+    })
+    .await
+}
+
+pub async fn add_role(name: &str, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.role_add(name).await.map(|_| ())
+    })
+    .await
+}
+
+pub async fn delete_role(name: &str, session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.role_delete(name).await.map(|_| ())
+    })
+    .await
+}
+
+pub async fn grant_permission(
+    role: &str,
+    permission: &PermissionRequest,
+    session: &Session,
+) -> Result<(), String> {
+    let (key, range_end) = permission.key_range()?;
+    let perm_type = permission.perm_type;
+    perform_op(session, |mut client| {
+        let (key, range_end) = (key.clone(), range_end.clone());
+        async move {
+            let mut permission = match perm_type {
+                PermissionKind::Read => Permission::read(key),
+                PermissionKind::Write => Permission::write(key),
+                PermissionKind::Readwrite => Permission::read_write(key),
+            };
+            if !range_end.is_empty() {
+                permission = permission.with_range_end(range_end);
+            }
+            client
+                .role_grant_permission(role, permission)
+                .await
+                .map(|_| ())
+        }
+    })
+    .await
+}
+
+/// Revoke the permission on exactly this key or range, whatever its type
+pub async fn revoke_permission(
+    role: &str,
+    permission: &PermissionRequest,
+    session: &Session,
+) -> Result<(), String> {
+    let (key, range_end) = permission.key_range()?;
+    perform_op(session, |mut client| {
+        let (key, range_end) = (key.clone(), range_end.clone());
+        async move {
+            let options = (!range_end.is_empty())
+                .then(|| RoleRevokePermissionOptions::new().with_range_end(range_end));
+            client
+                .role_revoke_permission(role, key, options)
+                .await
+                .map(|_| ())
+        }
+    })
+    .await
+}
+
+/// Enable authentication, once the `root` user exists with the `root` role.
+///
+/// Also refuses when the target profile has no credentials, as it would be locked out.
+pub async fn enable_auth(session: &Session) -> Result<(), String> {
+    if session.profile.user.is_none() {
+        return Err("Set a user on the profile before enabling authentication".to_string());
+    }
+
+    let users = list_users(session).await?;
+    let root_ready = users
+        .iter()
+        .any(|user| user.name == ROOT && user.roles.iter().any(|role| role == ROOT));
+    if !root_ready {
+        return Err(
+            "Authentication requires a 'root' user with the 'root' role, add it first".to_string(),
+        );
+    }
+
+    perform_op(session, |mut client| async move {
+        client.auth_enable().await.map(|_| ())
+    })
+    .await
+}
+
+pub async fn disable_auth(session: &Session) -> Result<(), String> {
+    perform_op(session, |mut client| async move {
+        client.auth_disable().await.map(|_| ())
+    })
+    .await
+}
+
+/// A role permission that covers the checked key
+#[derive(Serialize, Debug)]
+pub struct PermissionGrant {
+    pub role: String,
+    pub permission: PermissionInfo,
+}
+
+/// What a user may do on a key, and which roles allow it
+#[derive(Serialize, Debug)]
+pub struct EffectivePermission {
+    pub user: String,
+    pub roles: Vec<String>,
+    // The `root` role bypasses every permission
+    pub is_root: bool,
+    pub read: bool,
+    pub write: bool,
+    pub grants: Vec<PermissionGrant>,
+}
+
+/// Whether `permission` covers `key`, following etcd's range conventions
+fn permission_covers(permission: &Permission, key: &[u8]) -> bool {
+    let (start, end) = (permission.key(), permission.range_end());
+    match end {
+        // Single key
+        [] => key == start,
+        // Every key from `start` on
+        [0] => key >= start,
+        _ => key >= start && key < end,
+    }
+}
+
+/// Resolve the roles of `user` and the permissions they grant on `key`
+pub async fn check_permission(
+    user: &str,
+    key: &[u8],
+    session: &Session,
+) -> Result<EffectivePermission, String> {
+    perform_op(session, |mut client| async move {
+        let roles = client.user_get(user).await?.roles().to_vec();
+        let mut effective = EffectivePermission {
+            user: user.to_string(),
+            is_root: roles.iter().any(|role| role == ROOT),
+            read: false,
+            write: false,
+            grants: Vec::new(),
+            roles,
+        };
+
+        for role in &effective.roles {
+            let response = client.role_get(role.as_str()).await?;
+            for permission in response.permissions() {
+                if !permission_covers(permission, key) {
+                    continue;
+                }
+                let info = PermissionInfo::from(permission);
+                match info.perm_type {
+                    PermissionKind::Read => effective.read = true,
+                    PermissionKind::Write => effective.write = true,
+                    PermissionKind::Readwrite => {
+                        effective.read = true;
+                        effective.write = true;
+                    }
+                }
+                effective.grants.push(PermissionGrant {
+                    role: role.clone(),
+                    permission: info,
+                });
+            }
+        }
+
+        if effective.is_root {
+            effective.read = true;
+            effective.write = true;
+        }
+        Ok(effective)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_key_permission() {
+        let permission = Permission::read("/app/a");
+        assert!(permission_covers(&permission, b"/app/a"));
+        assert!(!permission_covers(&permission, b"/app/ab"));
+        assert!(!permission_covers(&permission, b"/app"));
+    }
+
+    #[test]
+    fn prefix_permission() {
+        let permission = Permission::read("/app/").with_range_end(range_end_of_prefix(b"/app/"));
+        assert!(permission_covers(&permission, b"/app/"));
+        assert!(permission_covers(&permission, b"/app/a/b"));
+        assert!(!permission_covers(&permission, b"/app"));
+        assert!(!permission_covers(&permission, b"/apq"));
+    }
+
+    #[test]
+    fn from_key_permission() {
+        let permission = Permission::write("/m").with_range_end(vec![0]);
+        assert!(permission_covers(&permission, b"/m"));
+        assert!(permission_covers(&permission, b"/z/any"));
+        assert!(!permission_covers(&permission, b"/a"));
+    }
+
+    #[test]
+    fn range_permission_excludes_its_end() {
+        let permission = Permission::read_write("a").with_range_end("c");
+        assert!(permission_covers(&permission, b"a"));
+        assert!(permission_covers(&permission, b"b/any"));
+        assert!(!permission_covers(&permission, b"c"));
+    }
+}
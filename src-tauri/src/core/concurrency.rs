@@ -0,0 +1,162 @@
+use etcd_client::{
+    Compare, CompareOp, GetOptions, LeaseTimeToLiveOptions, ObserveStream, SortOrder, SortTarget,
+    Txn, TxnOp,
+};
+use serde::Serialize;
+
+use crate::client::{Item, ItemKey};
+use crate::core::perform_op;
+use crate::state::Session;
+
+/// Contenders of a lock or an election, as created by etcd's concurrency APIs.
+///
+/// Each contender holds a key `<name>/<lease>`; the oldest one holds the lock
+/// (or leads the election) and the others wait in `create_revision` order.
+#[derive(Serialize, Debug, Default)]
+pub struct Contenders {
+    pub holder: Option<Contender>,
+    pub waiters: Vec<Contender>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Contender {
+    // The value is the proposal of an election candidate, empty for locks
+    pub item: Item,
+    // Remaining TTL of the contender's lease, -1 if it has expired
+    pub lease_ttl: Option<i64>,
+}
+
+/// Key prefix of the contenders of a lock or an election named `name`
+fn contenders_prefix(name: &[u8]) -> Vec<u8> {
+    let mut prefix = name.to_vec();
+    if !prefix.ends_with(b"/") {
+        prefix.push(b'/');
+    }
+    prefix
+}
+
+/// List the holder and the waiters of a lock or an election
+pub async fn list_contenders(name: &[u8], session: &Session) -> Result<Contenders, String> {
+    perform_op(session, |mut client| async move {
+        let prefix = contenders_prefix(name);
+        let options = GetOptions::new()
+            .with_prefix()
+            .with_sort(SortTarget::Create, SortOrder::Ascend);
+        let kvs = client.get(prefix, Some(options)).await?.take_kvs();
+
+        let mut contenders = Vec::with_capacity(kvs.len());
+        for kv in kvs {
+            let lease_ttl = match kv.lease() {
+                0 => None,
+                lease => Some(client.lease_time_to_live(lease, None).await?.ttl()),
+            };
+            contenders.push(Contender {
+                item: Item::from(kv),
+                lease_ttl,
+            });
+        }
+
+        let mut contenders = contenders.into_iter();
+        Ok(Contenders {
+            holder: contenders.next(),
+            waiters: contenders.collect(),
+        })
+    })
+    .await
+}
+
+/// Stream the leader of an election, starting with the current one
+pub async fn observe_election(name: &[u8], session: &Session) -> Result<ObserveStream, String> {
+    // The election API appends the separator itself
+    let name = name.strip_suffix(b"/").unwrap_or(name);
+    perform_op(
+        session,
+        |mut client| async move { client.observe(name).await },
+    )
+    .await
+}
+
+/// Outcome of [`force_release`]
+#[derive(Serialize, Debug)]
+pub struct ForceReleaseResult {
+    pub holder: Contender,
+    // Every key attached to the holder's lease, all deleted along with the lock key
+    pub deleted_keys: Vec<ItemKey>,
+}
+
+/// Release a lock (or end a leadership) held by a crashed or stuck process.
+///
+/// Revokes the holder's lease, which deletes every key attached to it and not only the
+/// lock key, so that the next waiter takes over. With `expected_holder`, fails unless
+/// that key still holds the lock, e.g. the holder the user confirmed releasing.
+pub async fn force_release(
+    name: &[u8],
+    expected_holder: Option<&[u8]>,
+    session: &Session,
+) -> Result<ForceReleaseResult, String> {
+    let holder = list_contenders(name, session)
+        .await?
+        .holder
+        .ok_or_else(|| format!("'{}' has no holder", String::from_utf8_lossy(name)))?;
+    let key = holder.item.key_encoding.decode(&holder.item.key)?;
+    if expected_holder.is_some_and(|expected| expected != key.as_slice()) {
+        return Err(format!(
+            "'{}' is now held by {}, list its contenders again",
+            String::from_utf8_lossy(name),
+            holder.item.key
+        ));
+    }
+
+    let (lease, create_revision) = (holder.item.lease, holder.item.create_revision);
+    // Only release the holder that was listed: had it released the lock meanwhile,
+    // the next holder would be released instead
+    let still_held = Compare::create_revision(key.clone(), CompareOp::Equal, create_revision);
+    if lease == 0 {
+        // Not created through the concurrency API, there is no lease to revoke
+        let released = perform_op(session, |mut client| {
+            let (key, still_held) = (key.clone(), still_held.clone());
+            async move {
+                let txn = Txn::new()
+                    .when([still_held])
+                    .and_then([TxnOp::delete(key, None)]);
+                client.txn(txn).await
+            }
+        })
+        .await?
+        .succeeded();
+        if !released {
+            return Err(format!("{} released the lock meanwhile", holder.item.key));
+        }
+        return Ok(ForceReleaseResult {
+            holder,
+            deleted_keys: vec![ItemKey::from(key)],
+        });
+    }
+
+    // Leases cannot be revoked in a transaction, check the holder right before instead
+    let (held, keys) = perform_op(session, |mut client| {
+        let still_held = still_held.clone();
+        async move {
+            let held = client.txn(Txn::new().when([still_held])).await?.succeeded();
+            let keys = client
+                .lease_time_to_live(lease, Some(LeaseTimeToLiveOptions::new().with_keys()))
+                .await?
+                .keys()
+                .to_vec();
+            Ok((held, keys))
+        }
+    })
+    .await?;
+    if !held {
+        return Err(format!("{} released the lock meanwhile", holder.item.key));
+    }
+    perform_op(session, |mut client| async move {
+        client.lease_revoke(lease).await
+    })
+    .await?;
+
+    Ok(ForceReleaseResult {
+        holder,
+        deleted_keys: keys.into_iter().map(ItemKey::from).collect(),
+    })
+}
@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+
+use etcd_client::{Client, Error, KeyValue, SortOrder, SortTarget};
+use serde::Serialize;
+
+use crate::client::Item;
+use crate::core::split_batch::{RawKvSplitter, execute_splittable};
+use crate::core::{perform_op, range_end_of_prefix};
+use crate::state::Session;
+
+/// Differences between two namespaces, from `left` to `right`
+#[derive(Serialize, Debug, Default)]
+pub struct DiffResult {
+    // Only on the right side
+    pub added: Vec<Item>,
+    // Only on the left side
+    pub removed: Vec<Item>,
+    pub changed: Vec<ChangedKey>,
+    pub unchanged: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChangedKey {
+    pub left: Item,
+    pub right: Item,
+}
+
+/// Same as [`DiffResult`], with the KV pairs as read from etcd
+pub(super) struct RawDiff<T = KeyValue> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+    pub changed: Vec<(T, T)>,
+    pub unchanged: usize,
+}
+
+impl<T> Default for RawDiff<T> {
+    fn default() -> Self {
+        RawDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+            unchanged: 0,
+        }
+    }
+}
+
+/// The key [`diff_sorted`] matches pairs on
+pub(super) trait DiffKey {
+    fn diff_key(&self) -> &[u8];
+}
+
+impl DiffKey for KeyValue {
+    fn diff_key(&self) -> &[u8] {
+        self.key()
+    }
+}
+
+impl From<RawDiff> for DiffResult {
+    fn from(diff: RawDiff) -> Self {
+        DiffResult {
+            added: diff.added.into_iter().map(Item::from).collect(),
+            removed: diff.removed.into_iter().map(Item::from).collect(),
+            changed: diff
+                .changed
+                .into_iter()
+                .map(|(left, right)| ChangedKey {
+                    left: Item::from(left),
+                    right: Item::from(right),
+                })
+                .collect(),
+            unchanged: diff.unchanged,
+        }
+    }
+}
+
+/// Compare every key under `left_prefix` on `left` with those under `right_prefix` on `right`.
+///
+/// With `ignore_prefix`, keys are matched on what follows their prefix, so that
+/// `/staging/a` and `/prod/a` are the same key.
+pub async fn diff_prefixes(
+    (left, left_prefix): (&Session, &[u8]),
+    (right, right_prefix): (&Session, &[u8]),
+    ignore_prefix: bool,
+) -> Result<DiffResult, String> {
+    let (left_kvs, right_kvs) = tokio::try_join!(
+        read_prefix(left, left_prefix),
+        read_prefix(right, right_prefix)
+    )?;
+    let (left_strip, right_strip) = if ignore_prefix {
+        (left_prefix.len(), right_prefix.len())
+    } else {
+        (0, 0)
+    };
+    Ok(
+        diff_sorted((left_kvs, left_strip), (right_kvs, right_strip), |l, r| {
+            l.value() == r.value()
+        })
+        .into(),
+    )
+}
+
+/// Every KV pair under `prefix` on the cluster of `session`, sorted by key
+pub(super) async fn read_prefix(session: &Session, prefix: &[u8]) -> Result<Vec<KeyValue>, String> {
+    perform_op(session, |mut client| async move {
+        fetch_prefix(&mut client, prefix).await
+    })
+    .await
+    .map_err(|e| format!("Failed to read from profile {}: {e}", session.profile.name))
+}
+
+/// Every KV pair under `prefix`, sorted by key
+async fn fetch_prefix(client: &mut Client, prefix: &[u8]) -> Result<Vec<KeyValue>, Error> {
+    execute_splittable(
+        client,
+        RawKvSplitter { revision: 0 },
+        (prefix, range_end_of_prefix(prefix)),
+        (SortTarget::Key, SortOrder::Ascend),
+    )
+    .await
+}
+
+/// Merge two lists of KV pairs sorted by key, matching keys once the first
+/// `strip` bytes of each side are removed. Matching pairs for which `same` is false
+/// are changed.
+pub(super) fn diff_sorted<T: DiffKey>(
+    (left, left_strip): (Vec<T>, usize),
+    (right, right_strip): (Vec<T>, usize),
+    same: impl Fn(&T, &T) -> bool,
+) -> RawDiff<T> {
+    // Stripping a prefix shared by the whole side keeps it sorted
+    let mut diff = RawDiff::default();
+    let mut left_iter = left.into_iter().peekable();
+    let mut right_iter = right.into_iter().peekable();
+    loop {
+        let order = match (left_iter.peek(), right_iter.peek()) {
+            (Some(l), Some(r)) => l.diff_key()[left_strip..].cmp(&r.diff_key()[right_strip..]),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match order {
+            Ordering::Less => diff.removed.extend(left_iter.next()),
+            Ordering::Greater => diff.added.extend(right_iter.next()),
+            Ordering::Equal => {
+                let (l, r) = (left_iter.next().unwrap(), right_iter.next().unwrap());
+                if same(&l, &r) {
+                    diff.unchanged += 1;
+                } else {
+                    diff.changed.push((l, r));
+                }
+            }
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Pair(&'static str, &'static str);
+
+    impl DiffKey for Pair {
+        fn diff_key(&self) -> &[u8] {
+            self.0.as_bytes()
+        }
+    }
+
+    fn same_value(l: &Pair, r: &Pair) -> bool {
+        l.1 == r.1
+    }
+
+    #[test]
+    fn diffs_sorted_pairs() {
+        let left = vec![Pair("a", "1"), Pair("b", "2"), Pair("d", "4")];
+        let right = vec![
+            Pair("b", "2"),
+            Pair("c", "3"),
+            Pair("d", "5"),
+            Pair("e", "6"),
+        ];
+        let diff = diff_sorted((left, 0), (right, 0), same_value);
+
+        assert_eq!(diff.removed, vec![Pair("a", "1")]);
+        assert_eq!(diff.added, vec![Pair("c", "3"), Pair("e", "6")]);
+        assert_eq!(diff.changed, vec![(Pair("d", "4"), Pair("d", "5"))]);
+        assert_eq!(diff.unchanged, 1);
+    }
+
+    #[test]
+    fn matches_keys_without_their_prefix() {
+        let left = vec![Pair("/staging/a", "1"), Pair("/staging/b", "2")];
+        let right = vec![Pair("/prod/a", "1"), Pair("/prod/b", "3")];
+
+        let diff = diff_sorted(
+            (left, "/staging/".len()),
+            (right, "/prod/".len()),
+            same_value,
+        );
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(
+            diff.changed,
+            vec![(Pair("/staging/b", "2"), Pair("/prod/b", "3"))]
+        );
+        assert_eq!(diff.unchanged, 1);
+    }
+
+    #[test]
+    fn uses_the_given_comparison() {
+        let diff = diff_sorted(
+            (vec![Pair("a", "1")], 0),
+            (vec![Pair("a", "2")], 0),
+            |_, _| true,
+        );
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.unchanged, 1);
+    }
+
+    #[test]
+    fn diffs_empty_sides() {
+        let diff = diff_sorted((Vec::new(), 0), (vec![Pair("a", "1")], 0), same_value);
+        assert_eq!(diff.added, vec![Pair("a", "1")]);
+        let diff = diff_sorted((vec![Pair("a", "1")], 0), (Vec::new(), 0), same_value);
+        assert_eq!(diff.removed, vec![Pair("a", "1")]);
+    }
+}
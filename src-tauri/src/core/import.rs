@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+
+use etcd_client::{Client, Compare, CompareOp, Error, GetOptions, Txn, TxnOp, TxnOpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::core::perform_op;
+use crate::dump::DumpEntry;
+use crate::state::Session;
+
+// etcd's defaults are 128 ops (`--max-txn-ops`) and 1.5 MiB (`--max-request-bytes`)
+// per transaction, leave some room for the compares and the request overhead
+const MAX_BATCH_OPS: usize = 64;
+const MAX_BATCH_BYTES: usize = 1024 * 1024;
+// Attempts at a batch whose keys keep changing while it is being written
+const MAX_BATCH_ATTEMPTS: usize = 3;
+
+/// What to do with keys of the import that already exist
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    #[default]
+    Skip,
+    Overwrite,
+    // Abort unless the existing value is identical to the imported one
+    FailIfDifferent,
+}
+
+/// Payload of the progress event emitted after every batch
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ImportProgress {
+    pub total: usize,
+    pub processed: usize,
+    pub written: usize,
+    pub skipped: usize,
+}
+
+/// Replace `from` with `to` at the start of every key.
+///
+/// Fails if a key does not start with `from`, rather than importing it in place.
+pub fn rewrite_prefix(entries: &mut [DumpEntry], from: &[u8], to: &[u8]) -> Result<(), String> {
+    for entry in entries.iter_mut() {
+        let Some(rest) = entry.key.strip_prefix(from) else {
+            return Err(format!(
+                "Key '{}' does not start with '{}'",
+                String::from_utf8_lossy(&entry.key),
+                String::from_utf8_lossy(from)
+            ));
+        };
+        entry.key = [to, rest].concat();
+    }
+    Ok(())
+}
+
+/// Write `entries` in transactions small enough for etcd's request limits.
+///
+/// Every batch is applied atomically, but batches written before a failure stay applied.
+/// With [`ConflictPolicy::FailIfDifferent`], every key is checked before the first write.
+pub async fn import_entries(
+    entries: &[DumpEntry],
+    policy: ConflictPolicy,
+    on_progress: impl Fn(&ImportProgress),
+    session: &Session,
+) -> Result<ImportProgress, String> {
+    let entries = dedupe_keys(entries);
+    let mut progress = ImportProgress {
+        total: entries.len(),
+        ..Default::default()
+    };
+    let batches = split_batches(&entries, |entry| entry.key.len() + entry.value.len());
+
+    if let ConflictPolicy::FailIfDifferent = policy {
+        for &batch in &batches {
+            perform_op(session, |mut client| async move {
+                let existing = read_existing(&mut client, batch, policy).await?;
+                ensure_identical(batch, &existing)
+            })
+            .await
+            .map_err(|e| format!("{e}, nothing was imported"))?;
+        }
+    }
+
+    for batch in batches {
+        let written = perform_op(session, |client| import_batch(client, batch, policy))
+            .await
+            .map_err(|e| {
+                format!(
+                    "{e} ({} of {} keys were imported before stopping)",
+                    progress.written, progress.total
+                )
+            })?;
+        progress.processed += batch.len();
+        progress.written += written;
+        progress.skipped += batch.len() - written;
+        on_progress(&progress);
+    }
+
+    Ok(progress)
+}
+
+/// Keep the last entry of every key, as a transaction may not write a key twice
+fn dedupe_keys(entries: &[DumpEntry]) -> Vec<&DumpEntry> {
+    let mut last_index = HashMap::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        last_index.insert(entry.key.as_slice(), i);
+    }
+    let deduped: Vec<_> = entries
+        .iter()
+        .enumerate()
+        .filter(|(i, entry)| last_index[entry.key.as_slice()] == *i)
+        .map(|(_, entry)| entry)
+        .collect();
+    if deduped.len() < entries.len() {
+        log::warn!(
+            "Ignoring {} duplicate keys of the import, the last value of each is kept",
+            entries.len() - deduped.len()
+        );
+    }
+    deduped
+}
+
+/// Cut `items` into batches that fit in a single transaction
+pub(super) fn split_batches<T>(items: &[T], size: impl Fn(&T) -> usize) -> Vec<&[T]> {
+    let mut batches = Vec::new();
+    let (mut start, mut bytes) = (0, 0);
+    for (i, item) in items.iter().enumerate() {
+        let size = size(item);
+        if i > start && (i - start >= MAX_BATCH_OPS || bytes + size > MAX_BATCH_BYTES) {
+            batches.push(&items[start..i]);
+            (start, bytes) = (i, 0);
+        }
+        bytes += size;
+    }
+    if start < items.len() {
+        batches.push(&items[start..]);
+    }
+    batches
+}
+
+/// Apply one batch, returns the number of keys written
+async fn import_batch(
+    mut client: Client,
+    batch: &[&DumpEntry],
+    policy: ConflictPolicy,
+) -> Result<usize, Error> {
+    if let ConflictPolicy::Overwrite = policy {
+        let puts: Vec<_> = batch
+            .iter()
+            .map(|entry| TxnOp::put(entry.key.clone(), entry.value.clone(), None))
+            .collect();
+        client.txn(Txn::new().and_then(puts)).await?;
+        return Ok(batch.len());
+    }
+
+    for attempt in 1..=MAX_BATCH_ATTEMPTS {
+        let existing = read_existing(&mut client, batch, policy).await?;
+        // Checked before the import too, but keys may have changed since
+        ensure_identical(batch, &existing)?;
+
+        let mut compares = Vec::new();
+        let mut puts = Vec::new();
+        for entry in batch {
+            if !existing.contains_key(entry.key.as_slice()) {
+                compares.push(Compare::create_revision(
+                    entry.key.clone(),
+                    CompareOp::Equal,
+                    0,
+                ));
+                puts.push(TxnOp::put(entry.key.clone(), entry.value.clone(), None));
+            }
+        }
+
+        if puts.is_empty() {
+            return Ok(0);
+        }
+        let written = puts.len();
+        // Keys created since they were read fail the compares, read them again
+        if client
+            .txn(Txn::new().when(compares).and_then(puts))
+            .await?
+            .succeeded()
+        {
+            return Ok(written);
+        }
+        log::warn!("Keys of the batch changed while importing (attempt {attempt})");
+    }
+
+    Err(Error::InvalidArgs(
+        "Keys keep changing while being imported".to_string(),
+    ))
+}
+
+/// Fail if a key of the batch exists with another value, `existing` being read with values
+fn ensure_identical(
+    batch: &[&DumpEntry],
+    existing: &HashMap<&[u8], Option<Vec<u8>>>,
+) -> Result<(), Error> {
+    for entry in batch {
+        let differs = existing
+            .get(entry.key.as_slice())
+            .and_then(Option::as_deref)
+            .is_some_and(|value| value != entry.value.as_slice());
+        if differs {
+            return Err(Error::InvalidArgs(format!(
+                "Key '{}' already exists with a different value",
+                String::from_utf8_lossy(&entry.key)
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Existing keys of the batch, along with their value when the policy needs it
+/// (only [`ConflictPolicy::FailIfDifferent`] compares values)
+async fn read_existing<'a>(
+    client: &mut Client,
+    batch: &[&'a DumpEntry],
+    policy: ConflictPolicy,
+) -> Result<HashMap<&'a [u8], Option<Vec<u8>>>, Error> {
+    let with_values = matches!(policy, ConflictPolicy::FailIfDifferent);
+    let gets: Vec<_> = batch
+        .iter()
+        .map(|entry| {
+            let options = (!with_values).then(|| GetOptions::new().with_keys_only());
+            TxnOp::get(entry.key.clone(), options)
+        })
+        .collect();
+    let response = client.txn(Txn::new().and_then(gets)).await?;
+
+    let mut existing = HashMap::new();
+    for (&entry, op) in batch.iter().zip(response.op_responses()) {
+        let TxnOpResponse::Get(get) = op else {
+            continue;
+        };
+        if let Some(kv) = get.kvs().first() {
+            existing.insert(
+                entry.key.as_slice(),
+                with_values.then(|| kv.value().to_vec()),
+            );
+        }
+    }
+    Ok(existing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, value: &str) -> DumpEntry {
+        DumpEntry {
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn keeps_the_last_duplicate() {
+        let entries = [entry("a", "1"), entry("b", "2"), entry("a", "3")];
+        let deduped: Vec<_> = dedupe_keys(&entries)
+            .into_iter()
+            .map(|entry| (entry.key.as_slice(), entry.value.as_slice()))
+            .collect();
+        assert_eq!(
+            deduped,
+            vec![(&b"b"[..], &b"2"[..]), (&b"a"[..], &b"3"[..])]
+        );
+    }
+
+    #[test]
+    fn splits_batches_on_op_count() {
+        let items = vec![1; MAX_BATCH_OPS * 2 + 1];
+        let sizes: Vec<_> = split_batches(&items, |_| 1)
+            .iter()
+            .map(|batch| batch.len())
+            .collect();
+        assert_eq!(sizes, vec![MAX_BATCH_OPS, MAX_BATCH_OPS, 1]);
+    }
+
+    #[test]
+    fn splits_batches_on_size() {
+        let items = [
+            MAX_BATCH_BYTES / 2,
+            MAX_BATCH_BYTES / 2,
+            1,
+            MAX_BATCH_BYTES * 2,
+            1,
+        ];
+        let batches = split_batches(&items, |size| *size);
+        // An item over the limit still gets a batch of its own
+        assert_eq!(
+            batches,
+            vec![&items[0..2], &items[2..3], &items[3..4], &items[4..5]]
+        );
+        assert!(split_batches(&[] as &[usize], |size| *size).is_empty());
+    }
+
+    #[test]
+    fn fails_on_different_existing_values() {
+        let (a, b) = (entry("a", "1"), entry("b", "2"));
+        let batch = [&a, &b];
+
+        let mut existing = HashMap::new();
+        existing.insert(&b"a"[..], Some(b"1".to_vec()));
+        assert!(ensure_identical(&batch, &existing).is_ok());
+
+        // Values are only read for the fail-if-different policy
+        existing.insert(&b"b"[..], None);
+        assert!(ensure_identical(&batch, &existing).is_ok());
+
+        existing.insert(&b"b"[..], Some(b"other".to_vec()));
+        assert!(ensure_identical(&batch, &existing).is_err());
+    }
+}
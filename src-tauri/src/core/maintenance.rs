@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use etcd_client::{Client, CompactionOptions, Member, StatusResponse};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::task::JoinSet;
+
+use crate::client::connect_endpoints;
+use crate::config::Profile;
+use crate::core::{MEMBER_STATUS_TIMEOUT, get_cluster_members, perform_op};
+use crate::state::Session;
+
+/// State of a member before or after a maintenance operation
+#[derive(Serialize, Debug)]
+pub struct MaintenanceStatus {
+    // Hex IDs, as printed by etcdctl
+    pub member_id: String,
+    pub leader_id: String,
+    pub revision: i64,
+    pub db_size: i64,
+    pub db_size_in_use: i64,
+    pub raft_applied_index: u64,
+}
+
+impl From<StatusResponse> for MaintenanceStatus {
+    fn from(status: StatusResponse) -> Self {
+        MaintenanceStatus {
+            member_id: format!("{:x}", status.header().map_or(0, |h| h.member_id())),
+            leader_id: format!("{:x}", status.leader()),
+            revision: status.header().map_or(0, |h| h.revision()),
+            db_size: status.db_size(),
+            db_size_in_use: status.db_size_in_use(),
+            raft_applied_index: status.raft_applied_index(),
+        }
+    }
+}
+
+/// Outcome of a maintenance operation, `after` is only set if it actually ran
+#[derive(Serialize, Debug)]
+pub struct MaintenanceResult {
+    pub dry_run: bool,
+    pub before: MaintenanceStatus,
+    pub after: Option<MaintenanceStatus>,
+}
+
+/// Hash of a member's key-value store, equal across healthy members at the same revision
+#[derive(Serialize, Debug)]
+pub struct MemberHash {
+    pub member_id: String,
+    pub hash: Option<u32>,
+    pub revision: i64,
+    pub compact_revision: i64,
+    // Hashes only cover the revisions after the compaction, so this one is not comparable
+    pub compact_revision_differs: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// `hash_kv` of every member, with whether they all agree
+#[derive(Serialize, Debug)]
+pub struct HashKvResult {
+    // Every member answered with a comparable hash, otherwise the outcome is unknown
+    pub complete: bool,
+    // Every member has the same hash, never true unless `complete`
+    pub consistent: bool,
+    pub members: Vec<MemberHash>,
+}
+
+async fn find_member(id: u64, session: &Session) -> Result<Member, String> {
+    get_cluster_members(session)
+        .await?
+        .into_iter()
+        .find(|member| member.id() == id)
+        .ok_or_else(|| format!("Member {id:x} not found"))
+}
+
+/// Connect to a single member, so that per-member operations reach it and only it
+async fn connect_member(profile: &Profile, member: &Member) -> Result<Client, String> {
+    if member.client_urls().is_empty() {
+        return Err(format!("Member {:x} has no client URL", member.id()));
+    }
+    connect_endpoints(profile, member.client_urls().to_vec()).await
+}
+
+async fn member_status(client: &mut Client) -> Result<MaintenanceStatus, String> {
+    client
+        .status()
+        .await
+        .map(MaintenanceStatus::from)
+        .map_err(|e| e.to_string())
+}
+
+/// Defragment a single member, which blocks it for the duration of the operation
+pub async fn defragment_member(
+    id: u64,
+    dry_run: bool,
+    session: &Session,
+) -> Result<MaintenanceResult, String> {
+    let member = find_member(id, session).await?;
+    let mut client = connect_member(&session.profile, &member).await?;
+
+    let before = member_status(&mut client).await?;
+    if dry_run {
+        return Ok(MaintenanceResult {
+            dry_run,
+            before,
+            after: None,
+        });
+    }
+    client
+        .defragment()
+        .await
+        .map_err(|e| format!("Failed to defragment member {id:x}: {e}"))?;
+    let after = member_status(&mut client).await?;
+
+    Ok(MaintenanceResult {
+        dry_run,
+        before,
+        after: Some(after),
+    })
+}
+
+/// Discard the history of every key before `revision`
+pub async fn compact(
+    revision: i64,
+    physical: bool,
+    dry_run: bool,
+    session: &Session,
+) -> Result<MaintenanceResult, String> {
+    let before = perform_op(session, |mut client| async move { client.status().await })
+        .await
+        .map(MaintenanceStatus::from)?;
+    if revision <= 0 || revision > before.revision {
+        return Err(format!(
+            "Cannot compact to revision {revision}, current revision is {}",
+            before.revision
+        ));
+    }
+    if dry_run {
+        return Ok(MaintenanceResult {
+            dry_run,
+            before,
+            after: None,
+        });
+    }
+
+    perform_op(session, |mut client| async move {
+        // Physical compaction waits until the data is actually removed from the backend
+        let options = physical.then(|| CompactionOptions::new().with_physical());
+        client.compact(revision, options).await
+    })
+    .await?;
+    let after = perform_op(session, |mut client| async move { client.status().await })
+        .await
+        .map(MaintenanceStatus::from)?;
+
+    Ok(MaintenanceResult {
+        dry_run,
+        before,
+        after: Some(after),
+    })
+}
+
+/// Hand leadership over to another voting member
+pub async fn move_leader(
+    target_id: u64,
+    dry_run: bool,
+    session: &Session,
+) -> Result<MaintenanceResult, String> {
+    let members = get_cluster_members(session).await?;
+    let target = members
+        .iter()
+        .find(|member| member.id() == target_id)
+        .ok_or_else(|| format!("Member {target_id:x} not found"))?;
+    if target.is_learner() {
+        return Err(format!(
+            "Member {target_id:x} is a learner and cannot become leader"
+        ));
+    }
+
+    let before = perform_op(session, |mut client| async move { client.status().await }).await?;
+    let leader_id = before.leader();
+    let before = MaintenanceStatus::from(before);
+    if leader_id == target_id {
+        return Err(format!("Member {target_id:x} is already the leader"));
+    }
+    if dry_run {
+        return Ok(MaintenanceResult {
+            dry_run,
+            before,
+            after: None,
+        });
+    }
+
+    // Only the current leader accepts a leadership transfer
+    let leader = members
+        .iter()
+        .find(|member| member.id() == leader_id)
+        .ok_or_else(|| format!("Leader {leader_id:x} is not a known member"))?;
+    let mut client = connect_member(&session.profile, leader).await?;
+    client
+        .move_leader(target_id)
+        .await
+        .map_err(|e| format!("Failed to move leader to {target_id:x}: {e}"))?;
+    let after = member_status(&mut client).await?;
+
+    Ok(MaintenanceResult {
+        dry_run,
+        before,
+        after: Some(after),
+    })
+}
+
+/// Compare the key-value store of every member at `revision` (0 for the current one)
+pub async fn hash_kv_all(revision: i64, session: &Session) -> Result<HashKvResult, String> {
+    let members = get_cluster_members(session).await?;
+    // Hash every member at the same revision, or writes in between would tell them apart
+    let revision = if revision > 0 {
+        revision
+    } else {
+        perform_op(session, |mut client| async move { client.status().await })
+            .await
+            .map(MaintenanceStatus::from)?
+            .revision
+    };
+
+    let mut tasks = JoinSet::new();
+    for (index, member) in members.into_iter().enumerate() {
+        let profile = session.profile.clone();
+        tasks.spawn(async move { (index, member_hash(&profile, &member, revision).await) });
+    }
+    let mut hashes: Vec<_> = tasks.join_all().await;
+    hashes.sort_by_key(|(index, _)| *index);
+    let mut hashes: Vec<_> = hashes.into_iter().map(|(_, hash)| hash).collect();
+
+    // Compare against the compact revision most members agree on
+    let mut compact_counts = HashMap::new();
+    for hash in hashes.iter().filter(|h| h.error.is_none()) {
+        *compact_counts.entry(hash.compact_revision).or_insert(0) += 1;
+    }
+    let common_compact = compact_counts
+        .into_iter()
+        .max_by_key(|&(compact_revision, count)| (count, compact_revision))
+        .map(|(compact_revision, _)| compact_revision);
+    for hash in hashes.iter_mut().filter(|h| h.error.is_none()) {
+        hash.compact_revision_differs = Some(hash.compact_revision) != common_compact;
+    }
+
+    let complete = !hashes.is_empty()
+        && hashes
+            .iter()
+            .all(|h| h.error.is_none() && !h.compact_revision_differs);
+    let first = hashes.first().and_then(|h| h.hash);
+    let consistent = complete && hashes.iter().all(|h| h.hash == first);
+
+    Ok(HashKvResult {
+        complete,
+        consistent,
+        members: hashes,
+    })
+}
+
+/// `hash_kv` of a single member, giving up after [`MEMBER_STATUS_TIMEOUT`]
+async fn member_hash(profile: &Profile, member: &Member, revision: i64) -> MemberHash {
+    let res = tokio::time::timeout(MEMBER_STATUS_TIMEOUT, async {
+        let mut client = connect_member(profile, member).await?;
+        client.hash_kv(revision).await.map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|_| Err("Timed out".to_string()));
+
+    let member_id = format!("{:x}", member.id());
+    match res {
+        Ok(response) => MemberHash {
+            member_id,
+            hash: Some(response.hash()),
+            revision: response.header().map_or(0, |h| h.revision()),
+            compact_revision: response.compact_revision(),
+            compact_revision_differs: false,
+            error: None,
+        },
+        Err(e) => {
+            log::warn!("Failed to get hash of member {member_id}: {e}");
+            MemberHash {
+                member_id,
+                hash: None,
+                revision: 0,
+                compact_revision: 0,
+                compact_revision_differs: false,
+                error: Some(e),
+            }
+        }
+    }
+}
+
+// Emit progress at most once per this many bytes, snapshots arrive in small chunks
+const SNAPSHOT_PROGRESS_STEP: u64 = 4 * 1024 * 1024;
+// etcd appends the SHA-256 of the database to the snapshot stream
+const SNAPSHOT_HASH_LEN: usize = 32;
+
+/// Payload of the progress event emitted while a snapshot is downloaded
+#[derive(Serialize, Clone, Debug)]
+pub struct SnapshotProgress {
+    pub received: u64,
+    // Size announced by the member plus its trailing checksum, 0 until the first chunk
+    pub total: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SnapshotResult {
+    pub path: String,
+    pub member_id: String,
+    pub size: u64,
+    // SHA-256 of the whole file, to check copies of the backup against
+    pub sha256: String,
+    pub revision: i64,
+}
+
+/// Download a snapshot of a member's database to `path`.
+///
+/// The snapshot is written next to `path` and only renamed once its size and
+/// embedded checksum have been verified.
+pub async fn snapshot_member(
+    id: u64,
+    path: &Path,
+    on_progress: impl Fn(&SnapshotProgress),
+    session: &Session,
+) -> Result<SnapshotResult, String> {
+    let member = find_member(id, session).await?;
+    let mut client = connect_member(&session.profile, &member).await?;
+
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    let part_path = std::path::PathBuf::from(part_path);
+
+    let res = download_snapshot(&mut client, &part_path, on_progress).await;
+    let (size, sha256, revision) = match res {
+        Ok(res) => res,
+        Err(e) => {
+            if let Err(err) = tokio::fs::remove_file(&part_path).await {
+                log::debug!("Failed to remove {}: {err}", part_path.display());
+            }
+            return Err(e);
+        }
+    };
+    tokio::fs::rename(&part_path, path)
+        .await
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+
+    Ok(SnapshotResult {
+        path: path.display().to_string(),
+        member_id: format!("{id:x}"),
+        size,
+        sha256,
+        revision,
+    })
+}
+
+/// Checks a snapshot stream as it arrives: its announced size and trailing checksum
+struct SnapshotCheck {
+    file_hash: Sha256,
+    // The database hash covers everything but the trailing checksum, which is only
+    // known once the stream ends, so the last bytes are held back
+    db_hash: Sha256,
+    tail: Vec<u8>,
+    progress: SnapshotProgress,
+}
+
+impl SnapshotCheck {
+    fn new() -> Self {
+        SnapshotCheck {
+            file_hash: Sha256::new(),
+            db_hash: Sha256::new(),
+            tail: Vec::with_capacity(SNAPSHOT_HASH_LEN * 2),
+            progress: SnapshotProgress {
+                received: 0,
+                total: 0,
+            },
+        }
+    }
+
+    fn update(&mut self, remaining_bytes: u64, blob: &[u8]) {
+        if self.progress.received == 0 {
+            // The announced size only covers the database, the checksum follows it
+            // as a chunk of its own
+            self.progress.total = remaining_bytes + blob.len() as u64 + SNAPSHOT_HASH_LEN as u64;
+        }
+        self.file_hash.update(blob);
+        self.tail.extend_from_slice(blob);
+        if self.tail.len() > SNAPSHOT_HASH_LEN {
+            let split = self.tail.len() - SNAPSHOT_HASH_LEN;
+            self.db_hash.update(&self.tail[..split]);
+            self.tail.drain(..split);
+        }
+        self.progress.received += blob.len() as u64;
+    }
+
+    /// Size and SHA-256 of the whole snapshot, once it is known to be complete
+    fn finish(self) -> Result<(u64, String), String> {
+        if self.progress.received != self.progress.total {
+            return Err(format!(
+                "Snapshot is incomplete: received {} of {} bytes",
+                self.progress.received, self.progress.total
+            ));
+        }
+        if self.tail.len() != SNAPSHOT_HASH_LEN
+            || self.db_hash.finalize().as_slice() != self.tail.as_slice()
+        {
+            return Err("Snapshot checksum does not match its content".to_string());
+        }
+        Ok((
+            self.progress.received,
+            hex::encode(self.file_hash.finalize()),
+        ))
+    }
+}
+
+async fn download_snapshot(
+    client: &mut Client,
+    part_path: &Path,
+    on_progress: impl Fn(&SnapshotProgress),
+) -> Result<(u64, String, i64), String> {
+    let file = tokio::fs::File::create(part_path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {e}", part_path.display()))?;
+    let mut out = BufWriter::new(file);
+    let mut stream = client.snapshot().await.map_err(|e| e.to_string())?;
+
+    let mut check = SnapshotCheck::new();
+    let mut revision = 0;
+    let mut last_reported = 0;
+
+    while let Some(response) = stream.message().await.map_err(|e| e.to_string())? {
+        let blob = response.blob();
+        if check.progress.received == 0 {
+            revision = response.header().map_or(0, |h| h.revision());
+        }
+        out.write_all(blob).await.map_err(|e| e.to_string())?;
+        check.update(response.remaining_bytes(), blob);
+
+        if check.progress.received - last_reported >= SNAPSHOT_PROGRESS_STEP {
+            last_reported = check.progress.received;
+            on_progress(&check.progress);
+        }
+    }
+    out.flush().await.map_err(|e| e.to_string())?;
+    out.into_inner()
+        .sync_all()
+        .await
+        .map_err(|e| e.to_string())?;
+    on_progress(&check.progress);
+
+    let (size, sha256) = check.finish()?;
+    Ok((size, sha256, revision))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Split `db` into chunks the way etcd streams it, followed by its checksum
+    fn feed(check: &mut SnapshotCheck, db: &[u8], chunk: usize) {
+        let mut sent = 0;
+        for blob in db.chunks(chunk) {
+            sent += blob.len();
+            check.update((db.len() - sent) as u64, blob);
+        }
+        check.update(0, &Sha256::digest(db));
+    }
+
+    #[test]
+    fn accepts_a_chunked_snapshot_ending_with_its_checksum() {
+        let db: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let mut check = SnapshotCheck::new();
+        feed(&mut check, &db, 64);
+
+        let mut whole = db.clone();
+        whole.extend_from_slice(&Sha256::digest(&db));
+        let (size, sha256) = check.finish().unwrap();
+        assert_eq!(size, whole.len() as u64);
+        assert_eq!(sha256, hex::encode(Sha256::digest(&whole)));
+    }
+
+    #[test]
+    fn rejects_a_snapshot_cut_short() {
+        let db = vec![7u8; 300];
+        let mut check = SnapshotCheck::new();
+        check.update(200, &db[..100]);
+        check.update(100, &db[100..200]);
+        check.update(0, &Sha256::digest(&db));
+
+        let err = check.finish().unwrap_err();
+        assert!(err.contains("incomplete"), "{err}");
+    }
+
+    #[test]
+    fn rejects_a_snapshot_with_a_wrong_checksum() {
+        let db = vec![7u8; 300];
+        let mut check = SnapshotCheck::new();
+        check.update(0, &db);
+        check.update(0, &Sha256::digest(b"something else"));
+
+        let err = check.finish().unwrap_err();
+        assert!(err.contains("checksum"), "{err}");
+    }
+}
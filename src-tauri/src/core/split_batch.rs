@@ -3,7 +3,7 @@ use std::fmt::Debug;
 
 use etcd_client::{Client, Error, GetOptions, SortOrder, SortTarget};
 
-use crate::client::Item;
+use crate::client::{Item, ItemKey};
 
 struct BatchTask {
     from_key: Vec<u8>,
@@ -21,6 +21,14 @@ pub trait Splittable {
 
     /// Map KeyValue vector to output type
     fn map_kvs(&self, kvs: Vec<etcd_client::KeyValue>) -> impl Iterator<Item = Self::Output>;
+
+    /// Whether a key too large to fetch on its own fails the whole read.
+    ///
+    /// Listings stop at it, but reads whose results get written or compared elsewhere
+    /// must fail instead of silently missing keys.
+    fn fail_on_oversized_keys(&self) -> bool {
+        false
+    }
 }
 
 /// Execute a splittable query with OutOfRange retry logic
@@ -30,6 +38,31 @@ pub async fn execute_splittable<S: Splittable>(
     range: (impl Into<Vec<u8>>, impl Into<Vec<u8>>),
     sort: (SortTarget, SortOrder),
 ) -> Result<Vec<S::Output>, Error> {
+    let mut results = Vec::new();
+    for_each_batch(client, splitter, range, sort, |batch| {
+        results.extend(batch);
+        std::future::ready(Ok(()))
+    })
+    .await?;
+    log::debug!("Successfully fetched {} items", results.len());
+    Ok(results)
+}
+
+/// Like [`execute_splittable`], but hands every batch to `on_batch` in key order
+/// instead of collecting the whole range in memory. The next batch is only fetched
+/// once the future returned by `on_batch` completes.
+pub async fn for_each_batch<S, F, Fut>(
+    client: &mut Client,
+    splitter: S,
+    range: (impl Into<Vec<u8>>, impl Into<Vec<u8>>),
+    sort: (SortTarget, SortOrder),
+    mut on_batch: F,
+) -> Result<(), Error>
+where
+    S: Splittable,
+    F: FnMut(Vec<S::Output>) -> Fut,
+    Fut: Future<Output = std::io::Result<()>>,
+{
     let (start_key, range_end) = (range.0.into(), range.1.into());
     let (sort_target, sort_order) = sort;
 
@@ -46,7 +79,6 @@ pub async fn execute_splittable<S: Splittable>(
         .map(|res| res.count())?;
     log::debug!("Total keys: {}", count);
 
-    let mut results = Vec::new();
     let mut tasks = LinkedList::new();
     tasks.push_back(BatchTask {
         from_key: start_key,
@@ -83,17 +115,34 @@ pub async fn execute_splittable<S: Splittable>(
                     continue;
                 }
                 if res.more() {
-                    let last_key = kvs
+                    // Continue right after the last key so it is not fetched twice
+                    let mut next_key = kvs
                         .last()
                         .expect("Result should have at least one item")
                         .key()
                         .to_owned();
+                    next_key.push(0);
                     tasks.push_back(BatchTask {
-                        from_key: last_key,
+                        from_key: next_key,
                         limit: task.limit * 2,
                     });
                 }
-                results.extend(splitter.map_kvs(kvs));
+                on_batch(splitter.map_kvs(kvs).collect()).await?;
+            }
+            Err(e) if is_compacted_error(&e) => {
+                // Splitting cannot help, the pinned revision is gone for every batch
+                log::error!(
+                    "Batch starting at '{}' was read at a compacted revision: {}",
+                    String::from_utf8_lossy(&task.from_key),
+                    e
+                );
+                let message = match &e {
+                    Error::GRpcStatus(status) => status.message().to_owned(),
+                    e => e.to_string(),
+                };
+                return Err(Error::GRpcStatus(tonic::Status::out_of_range(format!(
+                    "{message}, the keys changed while they were read, try again"
+                ))));
             }
             e if is_out_of_range_error(&e) => {
                 log::info!(
@@ -102,6 +151,14 @@ pub async fn execute_splittable<S: Splittable>(
                     task.limit
                 );
                 if task.limit <= 1 {
+                    if splitter.fail_on_oversized_keys() {
+                        let key = first_key(client, &task.from_key, &range_end).await?;
+                        let key_display = String::from_utf8_lossy(&key);
+                        log::error!("Key '{}' is too large to be fetched", key_display);
+                        return Err(Error::GRpcStatus(tonic::Status::out_of_range(format!(
+                            "Key '{key_display}' is too large to be fetched"
+                        ))));
+                    }
                     log::error!(
                         "Batch size reduced to 1 but still out of range, skipping key '{}'",
                         String::from_utf8_lossy(&task.from_key)
@@ -119,8 +176,31 @@ pub async fn execute_splittable<S: Splittable>(
             }
         }
     }
-    log::debug!("Successfully fetched {} items", results.len());
-    Ok(results)
+    Ok(())
+}
+
+/// The first key of `from_key..range_end`, fetched without its value
+async fn first_key(
+    client: &mut Client,
+    from_key: &[u8],
+    range_end: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut res = client
+        .get(
+            from_key,
+            GetOptions::new()
+                .with_serializable()
+                .with_range(range_end)
+                .with_keys_only()
+                .with_limit(1)
+                .into(),
+        )
+        .await?;
+    Ok(res
+        .take_kvs()
+        .into_iter()
+        .next()
+        .map_or_else(|| from_key.to_vec(), |kv| kv.key().to_vec()))
 }
 
 /// Splitter for list_items (full KV pairs with prefix)
@@ -130,23 +210,7 @@ impl Splittable for KvSplitter {
     type Output = Item;
 
     fn map_kvs(&self, kvs: Vec<etcd_client::KeyValue>) -> impl Iterator<Item = Self::Output> {
-        kvs.into_iter().filter_map(|kv| {
-            if let (Ok(key_str), Ok(value_str)) = (
-                std::str::from_utf8(kv.key()),
-                std::str::from_utf8(kv.value()),
-            ) {
-                Some(Item {
-                    key: key_str.to_owned(),
-                    value: value_str.to_owned(),
-                    version: kv.version(),
-                    create_revision: kv.create_revision(),
-                    mod_revision: kv.mod_revision(),
-                    lease: kv.lease(),
-                })
-            } else {
-                None
-            }
-        })
+        kvs.into_iter().map(Item::from)
     }
 }
 
@@ -154,17 +218,14 @@ impl Splittable for KvSplitter {
 pub struct KeysOnlySplitter;
 
 impl Splittable for KeysOnlySplitter {
-    type Output = String;
+    type Output = ItemKey;
 
     fn get_options(&self) -> GetOptions {
         GetOptions::new().with_keys_only()
     }
 
     fn map_kvs(&self, kvs: Vec<etcd_client::KeyValue>) -> impl Iterator<Item = Self::Output> {
-        kvs.into_iter()
-            .map(|kv| kv.into_key_value().0)
-            .map(String::from_utf8)
-            .filter_map(Result::ok)
+        kvs.into_iter().map(ItemKey::from)
     }
 }
 
@@ -175,28 +236,88 @@ impl Splittable for ValuesInRangeSplitter {
     type Output = Item;
 
     fn map_kvs(&self, kvs: Vec<etcd_client::KeyValue>) -> impl Iterator<Item = Self::Output> {
-        kvs.into_iter().filter_map(|kv| {
-            match (
-                std::str::from_utf8(kv.key()),
-                std::str::from_utf8(kv.value()),
-            ) {
-                (Ok(key_str), Ok(value_str)) => Some(Item {
-                    key: key_str.to_owned(),
-                    value: value_str.to_owned(),
-                    version: kv.version(),
-                    create_revision: kv.create_revision(),
-                    mod_revision: kv.mod_revision(),
-                    lease: kv.lease(),
-                }),
-                _ => None,
-            }
-        })
+        kvs.into_iter().map(Item::from)
+    }
+}
+
+/// Splitter for dumps and diffs (raw KV pairs, all read at the same revision)
+pub struct RawKvSplitter {
+    // 0 reads each batch at the latest revision
+    pub revision: i64,
+}
+
+impl Splittable for RawKvSplitter {
+    type Output = etcd_client::KeyValue;
+
+    fn get_options(&self) -> GetOptions {
+        GetOptions::new().with_revision(self.revision)
+    }
+
+    fn map_kvs(&self, kvs: Vec<etcd_client::KeyValue>) -> impl Iterator<Item = Self::Output> {
+        kvs.into_iter()
+    }
+
+    fn fail_on_oversized_keys(&self) -> bool {
+        true
     }
 }
 
+/// Whether a read failed because its response was too large to be sent at once
 pub fn is_out_of_range_error<T: Debug>(res: &Result<T, etcd_client::Error>) -> bool {
     matches!(
         res,
-        Err(etcd_client::Error::GRpcStatus(status)) if status.code() == tonic::Code::OutOfRange
+        Err(etcd_client::Error::GRpcStatus(status))
+            if status.code() == tonic::Code::OutOfRange && !is_revision_message(status.message())
     )
 }
+
+/// Whether a read failed because its revision has been compacted
+pub fn is_compacted_error(e: &etcd_client::Error) -> bool {
+    matches!(
+        e,
+        etcd_client::Error::GRpcStatus(status)
+            if status.code() == tonic::Code::OutOfRange
+                && status.message().contains("revision has been compacted")
+    )
+}
+
+// etcd answers OutOfRange for reads at a compacted or future revision as well,
+// which "etcdserver: mvcc: required revision ..." tells apart
+fn is_revision_message(message: &str) -> bool {
+    message.contains("required revision")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: tonic::Code, message: &str) -> Result<(), Error> {
+        Err(Error::GRpcStatus(tonic::Status::new(code, message)))
+    }
+
+    #[test]
+    fn tells_compaction_from_oversized_responses() {
+        let compacted = status(
+            tonic::Code::OutOfRange,
+            "etcdserver: mvcc: required revision has been compacted",
+        );
+        assert!(!is_out_of_range_error(&compacted));
+        assert!(is_compacted_error(compacted.as_ref().unwrap_err()));
+
+        let future = status(
+            tonic::Code::OutOfRange,
+            "etcdserver: mvcc: required revision is a future revision",
+        );
+        assert!(!is_out_of_range_error(&future));
+        assert!(!is_compacted_error(future.as_ref().unwrap_err()));
+
+        let too_large = status(tonic::Code::OutOfRange, "message too large");
+        assert!(is_out_of_range_error(&too_large));
+        assert!(!is_compacted_error(too_large.as_ref().unwrap_err()));
+
+        assert!(!is_out_of_range_error(&status(
+            tonic::Code::Unavailable,
+            ""
+        )));
+    }
+}
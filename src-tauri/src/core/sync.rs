@@ -0,0 +1,430 @@
+use std::collections::{HashMap, HashSet};
+
+use etcd_client::{KeyValue, PutOptions, Txn, TxnOp};
+use serde::Serialize;
+
+use crate::client::ItemKey;
+use crate::core::diff::{DiffKey, diff_sorted, read_prefix};
+use crate::core::import::split_batches;
+use crate::core::perform_op;
+use crate::state::Session;
+
+/// What a sync should copy and how
+#[derive(Debug, Default)]
+pub struct SyncOptions {
+    // Only sync these keys, relative to the source prefix
+    pub keys: Option<Vec<Vec<u8>>>,
+    pub dry_run: bool,
+    // Delete keys under the target prefix that are not in the source
+    pub delete_extraneous: bool,
+    // Attach copied keys to a lease with the same remaining TTL as in the source
+    pub preserve_lease: bool,
+}
+
+/// Keys written (or that would be written) to the target
+#[derive(Serialize, Debug, Default)]
+pub struct SyncResult {
+    pub dry_run: bool,
+    pub copied: Vec<ItemKey>,
+    pub deleted: Vec<ItemKey>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum SyncOp {
+    Put {
+        key: Vec<u8>,
+        value: Vec<u8>,
+        lease: i64,
+    },
+    Delete {
+        key: Vec<u8>,
+    },
+}
+
+impl SyncOp {
+    fn key(&self) -> &[u8] {
+        match self {
+            SyncOp::Put { key, .. } | SyncOp::Delete { key } => key,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            SyncOp::Put { key, value, .. } => key.len() + value.len(),
+            SyncOp::Delete { key } => key.len(),
+        }
+    }
+}
+
+/// Copy the keys under `source_prefix` on `source` to `target_prefix` on `target`.
+///
+/// Only missing and different keys are written, in transactions small enough for
+/// etcd's request limits. With `preserve_lease`, keys attached to a different lease
+/// are different too.
+pub async fn sync_prefix(
+    (source, source_prefix): (&Session, &[u8]),
+    (target, target_prefix): (&Session, &[u8]),
+    options: &SyncOptions,
+) -> Result<SyncResult, String> {
+    if !options.dry_run {
+        target.ensure_unlocked()?;
+    }
+
+    let (source_kvs, target_kvs) = tokio::try_join!(
+        read_prefix(source, source_prefix),
+        read_prefix(target, target_prefix)
+    )?;
+    let same_cluster = if options.preserve_lease {
+        is_same_cluster(source, target).await?
+    } else {
+        false
+    };
+
+    let (ops, unchanged) = plan_sync(
+        (source_kvs, source_prefix),
+        (target_kvs, target_prefix),
+        options,
+        same_cluster,
+    );
+
+    let mut result = SyncResult {
+        dry_run: options.dry_run,
+        unchanged,
+        ..Default::default()
+    };
+    for op in &ops {
+        let key = ItemKey::from(op.key().to_vec());
+        match op {
+            SyncOp::Put { .. } => result.copied.push(key),
+            SyncOp::Delete { .. } => result.deleted.push(key),
+        }
+    }
+    if options.dry_run {
+        return Ok(result);
+    }
+
+    let leases = if options.preserve_lease {
+        map_leases(source, target, &ops, same_cluster)
+            .await
+            .map_err(|e| format!("Failed to copy leases: {e}"))?
+    } else {
+        HashMap::new()
+    };
+
+    let mut applied = 0;
+    for batch in split_batches(&ops, SyncOp::size) {
+        let leases = &leases;
+        perform_op(target, |mut client| async move {
+            let txn_ops: Vec<_> = batch
+                .iter()
+                .map(|op| match op {
+                    SyncOp::Put { key, value, lease } => {
+                        let options = leases
+                            .get(lease)
+                            .map(|lease| PutOptions::new().with_lease(*lease));
+                        TxnOp::put(key.clone(), value.clone(), options)
+                    }
+                    SyncOp::Delete { key } => TxnOp::delete(key.clone(), None),
+                })
+                .collect();
+            client.txn(Txn::new().and_then(txn_ops)).await
+        })
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to write to profile {}: {e} ({applied} of {} changes were applied \
+                 before stopping)",
+                target.profile.name,
+                ops.len()
+            )
+        })?;
+        applied += batch.len();
+    }
+
+    Ok(result)
+}
+
+/// What [`plan_sync`] reads of a KV pair
+trait SyncKv: DiffKey {
+    fn value(&self) -> &[u8];
+    fn lease(&self) -> i64;
+}
+
+impl SyncKv for KeyValue {
+    fn value(&self) -> &[u8] {
+        KeyValue::value(self)
+    }
+
+    fn lease(&self) -> i64 {
+        KeyValue::lease(self)
+    }
+}
+
+/// The writes that bring the target in line with the source, along with the number
+/// of selected keys that already are
+fn plan_sync<T: SyncKv>(
+    (source_kvs, source_prefix): (Vec<T>, &[u8]),
+    (target_kvs, target_prefix): (Vec<T>, &[u8]),
+    options: &SyncOptions,
+    same_cluster: bool,
+) -> (Vec<SyncOp>, usize) {
+    let selected = options
+        .keys
+        .as_ref()
+        .map(|keys| keys.iter().map(Vec::as_slice).collect::<HashSet<_>>());
+    let is_selected = |kv: &T, prefix: &[u8]| {
+        selected
+            .as_ref()
+            .is_none_or(|keys| keys.contains(&kv.diff_key()[prefix.len()..]))
+    };
+    let source_kvs: Vec<T> = source_kvs
+        .into_iter()
+        .filter(|kv| is_selected(kv, source_prefix))
+        .collect();
+    let target_kvs: Vec<T> = target_kvs
+        .into_iter()
+        .filter(|kv| is_selected(kv, target_prefix))
+        .collect();
+    let diff = diff_sorted(
+        (source_kvs, source_prefix.len()),
+        (target_kvs, target_prefix.len()),
+        |source_kv, target_kv| {
+            source_kv.value() == target_kv.value()
+                && (!options.preserve_lease
+                    || same_lease(source_kv.lease(), target_kv.lease(), same_cluster))
+        },
+    );
+
+    let to_target_key = |kv: &T| [target_prefix, &kv.diff_key()[source_prefix.len()..]].concat();
+    let mut ops: Vec<SyncOp> = diff
+        .removed
+        .iter()
+        .chain(diff.changed.iter().map(|(source_kv, _)| source_kv))
+        .map(|kv| SyncOp::Put {
+            key: to_target_key(kv),
+            value: kv.value().to_vec(),
+            lease: kv.lease(),
+        })
+        .collect();
+    if options.delete_extraneous {
+        ops.extend(diff.added.iter().map(|kv| SyncOp::Delete {
+            key: kv.diff_key().to_vec(),
+        }));
+    }
+    (ops, diff.unchanged)
+}
+
+/// Whether both sessions are connected to the same etcd cluster
+async fn is_same_cluster(source: &Session, target: &Session) -> Result<bool, String> {
+    let cluster_id = |session: &Session| {
+        perform_op(session, |mut client| async move {
+            client
+                .status()
+                .await
+                .map(|status| status.header().map(|h| h.cluster_id()))
+        })
+    };
+    let (source_id, target_id) = tokio::try_join!(cluster_id(source), cluster_id(target))
+        .map_err(|e| format!("Failed to get cluster status: {e}"))?;
+    Ok(source_id == target_id)
+}
+
+/// Whether a key attached to `source_lease` and one attached to `target_lease` are
+/// synced as far as leases go.
+///
+/// Lease IDs only mean something within a cluster, across clusters leases are
+/// recreated so only the presence of one can be compared.
+fn same_lease(source_lease: i64, target_lease: i64, same_cluster: bool) -> bool {
+    if same_cluster {
+        source_lease == target_lease
+    } else {
+        (source_lease != 0) == (target_lease != 0)
+    }
+}
+
+/// Map the leases of copied keys to leases usable on the target.
+///
+/// Leases are kept as is within a cluster, and recreated with their remaining TTL
+/// across clusters. Expired leases are left out, so their keys are copied without one.
+async fn map_leases(
+    source: &Session,
+    target: &Session,
+    ops: &[SyncOp],
+    same_cluster: bool,
+) -> Result<HashMap<i64, i64>, String> {
+    let source_leases: HashSet<i64> = ops
+        .iter()
+        .filter_map(|op| match op {
+            SyncOp::Put { lease, .. } if *lease != 0 => Some(*lease),
+            _ => None,
+        })
+        .collect();
+    if source_leases.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut leases = HashMap::new();
+    for id in source_leases {
+        if same_cluster {
+            leases.insert(id, id);
+            continue;
+        }
+        let ttl = perform_op(source, |mut client| async move {
+            client.lease_time_to_live(id, None).await
+        })
+        .await?
+        .ttl();
+        if ttl <= 0 {
+            log::warn!("Lease {id} has expired, copying its keys without a lease");
+            continue;
+        }
+        let (mut client, _) = target.client().await?;
+        // Not retried, a grant that went through would leave a stray lease
+        let granted = client
+            .lease_grant(ttl, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        log::debug!("Recreated lease {id} as {} with TTL {ttl}", granted.id());
+        leases.insert(id, granted.id());
+    }
+    Ok(leases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Key, value and lease
+    struct Kv(&'static str, &'static str, i64);
+
+    impl DiffKey for Kv {
+        fn diff_key(&self) -> &[u8] {
+            self.0.as_bytes()
+        }
+    }
+
+    impl SyncKv for Kv {
+        fn value(&self) -> &[u8] {
+            self.1.as_bytes()
+        }
+
+        fn lease(&self) -> i64 {
+            self.2
+        }
+    }
+
+    fn put(key: &str, value: &str, lease: i64) -> SyncOp {
+        SyncOp::Put {
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            lease,
+        }
+    }
+
+    fn delete(key: &str) -> SyncOp {
+        SyncOp::Delete {
+            key: key.as_bytes().to_vec(),
+        }
+    }
+
+    fn plan(options: &SyncOptions, same_cluster: bool) -> (Vec<SyncOp>, usize) {
+        let source = vec![
+            Kv("/src/a", "1", 0),
+            Kv("/src/b", "2", 0),
+            Kv("/src/c", "3", 0),
+        ];
+        let target = vec![
+            Kv("/dst/b", "2", 0),
+            Kv("/dst/c", "changed", 0),
+            Kv("/dst/d", "4", 0),
+        ];
+        plan_sync(
+            (source, b"/src/"),
+            (target, b"/dst/"),
+            options,
+            same_cluster,
+        )
+    }
+
+    #[test]
+    fn compares_lease_ids_within_a_cluster() {
+        assert!(same_lease(0, 0, true));
+        assert!(same_lease(5, 5, true));
+        assert!(!same_lease(5, 7, true));
+        assert!(!same_lease(5, 0, true));
+    }
+
+    #[test]
+    fn compares_lease_presence_across_clusters() {
+        assert!(same_lease(0, 0, false));
+        assert!(same_lease(5, 7, false));
+        assert!(!same_lease(5, 0, false));
+        assert!(!same_lease(0, 7, false));
+    }
+
+    #[test]
+    fn puts_missing_and_changed_keys() {
+        let (ops, unchanged) = plan(&SyncOptions::default(), false);
+        assert_eq!(ops, vec![put("/dst/a", "1", 0), put("/dst/c", "3", 0)]);
+        assert_eq!(unchanged, 1);
+    }
+
+    #[test]
+    fn deletes_extraneous_keys_on_request() {
+        let options = SyncOptions {
+            delete_extraneous: true,
+            ..Default::default()
+        };
+        let (ops, unchanged) = plan(&options, false);
+        assert_eq!(
+            ops,
+            vec![
+                put("/dst/a", "1", 0),
+                put("/dst/c", "3", 0),
+                delete("/dst/d")
+            ]
+        );
+        assert_eq!(unchanged, 1);
+    }
+
+    #[test]
+    fn only_syncs_selected_keys() {
+        let options = SyncOptions {
+            keys: Some(vec![b"a".to_vec(), b"b".to_vec(), b"d".to_vec()]),
+            delete_extraneous: true,
+            ..Default::default()
+        };
+        let (ops, unchanged) = plan(&options, false);
+        assert_eq!(ops, vec![put("/dst/a", "1", 0), delete("/dst/d")]);
+        assert_eq!(unchanged, 1);
+    }
+
+    #[test]
+    fn compares_leases_only_when_preserving_them() {
+        let plan_leases = |source_lease, target_lease, options: &SyncOptions, same_cluster| {
+            plan_sync(
+                (vec![Kv("/src/a", "1", source_lease)], b"/src/"),
+                (vec![Kv("/dst/a", "1", target_lease)], b"/dst/"),
+                options,
+                same_cluster,
+            )
+            .0
+        };
+        let preserve = SyncOptions {
+            preserve_lease: true,
+            ..Default::default()
+        };
+
+        assert!(plan_leases(5, 0, &SyncOptions::default(), false).is_empty());
+        assert_eq!(
+            plan_leases(5, 0, &preserve, false),
+            vec![put("/dst/a", "1", 5)]
+        );
+        assert!(plan_leases(5, 7, &preserve, false).is_empty());
+        assert_eq!(
+            plan_leases(5, 7, &preserve, true),
+            vec![put("/dst/a", "1", 5)]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use etcd_client::{
+    Compare, CompareOp, DeleteOptions, GetOptions, PutOptions, Txn, TxnOp, TxnOpResponse,
+    TxnResponse,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::client::{Encoding, Item};
+use crate::core::range_end_of_prefix;
+
+/// A transaction as described by the UI: `success` runs if every compare holds,
+/// `failure` otherwise
+#[derive(Deserialize, Debug)]
+pub struct TxnRequest {
+    #[serde(default)]
+    pub compare: Vec<TxnCompare>,
+    #[serde(default)]
+    pub success: Vec<TxnRequestOp>,
+    #[serde(default)]
+    pub failure: Vec<TxnRequestOp>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TxnCompare {
+    pub key: String,
+    #[serde(default)]
+    pub key_encoding: Encoding,
+    // Compare every key starting with `key`
+    #[serde(default)]
+    pub prefix: bool,
+    pub op: TxnCompareOp,
+    #[serde(flatten)]
+    pub target: TxnCompareTarget,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TxnCompareOp {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+}
+
+impl From<TxnCompareOp> for CompareOp {
+    fn from(op: TxnCompareOp) -> Self {
+        match op {
+            TxnCompareOp::Equal => CompareOp::Equal,
+            TxnCompareOp::NotEqual => CompareOp::NotEqual,
+            TxnCompareOp::Greater => CompareOp::Greater,
+            TxnCompareOp::Less => CompareOp::Less,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "target", rename_all = "snake_case")]
+pub enum TxnCompareTarget {
+    Value {
+        value: String,
+        #[serde(default)]
+        value_encoding: Encoding,
+    },
+    Version {
+        version: i64,
+    },
+    CreateRevision {
+        revision: i64,
+    },
+    ModRevision {
+        revision: i64,
+    },
+    Lease {
+        lease: i64,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TxnRequestOp {
+    Put {
+        key: String,
+        #[serde(default)]
+        key_encoding: Encoding,
+        value: String,
+        #[serde(default)]
+        value_encoding: Encoding,
+        lease: Option<i64>,
+        #[serde(default)]
+        prev_kv: bool,
+    },
+    Delete {
+        key: String,
+        #[serde(default)]
+        key_encoding: Encoding,
+        #[serde(default)]
+        prefix: bool,
+        #[serde(default)]
+        prev_kv: bool,
+    },
+    Get {
+        key: String,
+        #[serde(default)]
+        key_encoding: Encoding,
+        #[serde(default)]
+        prefix: bool,
+    },
+}
+
+/// Outcome of a transaction, with one entry per executed op
+#[derive(Serialize, Debug)]
+pub struct TxnResult {
+    pub succeeded: bool,
+    pub revision: i64,
+    pub responses: Vec<TxnOpResult>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TxnOpResult {
+    Put { prev_kv: Option<Item> },
+    Delete { deleted: i64, prev_kvs: Vec<Item> },
+    Get { kvs: Vec<Item>, count: i64 },
+}
+
+impl TxnRequest {
+    /// Decode keys and values and build the etcd transaction
+    pub fn build(&self) -> Result<Txn, String> {
+        let compares = self
+            .compare
+            .iter()
+            .map(TxnCompare::build)
+            .collect::<Result<Vec<_>, _>>()?;
+        let success = self
+            .success
+            .iter()
+            .map(TxnRequestOp::build)
+            .collect::<Result<Vec<_>, _>>()?;
+        let failure = self
+            .failure
+            .iter()
+            .map(TxnRequestOp::build)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Txn::new().when(compares).and_then(success).or_else(failure))
+    }
+}
+
+impl TxnCompare {
+    fn build(&self) -> Result<Compare, String> {
+        let key = self.key_encoding.decode(&self.key)?;
+        let range_end = self.prefix.then(|| range_end_of_prefix(&key));
+        let op = self.op.into();
+        let compare = match &self.target {
+            TxnCompareTarget::Value {
+                value,
+                value_encoding,
+            } => Compare::value(key, op, value_encoding.decode(value)?),
+            TxnCompareTarget::Version { version } => Compare::version(key, op, *version),
+            TxnCompareTarget::CreateRevision { revision } => {
+                Compare::create_revision(key, op, *revision)
+            }
+            TxnCompareTarget::ModRevision { revision } => Compare::mod_revision(key, op, *revision),
+            TxnCompareTarget::Lease { lease } => Compare::lease(key, op, *lease),
+        };
+
+        Ok(match range_end {
+            Some(range_end) => compare.with_range(range_end),
+            None => compare,
+        })
+    }
+}
+
+impl TxnRequestOp {
+    fn build(&self) -> Result<TxnOp, String> {
+        Ok(match self {
+            TxnRequestOp::Put {
+                key,
+                key_encoding,
+                value,
+                value_encoding,
+                lease,
+                prev_kv,
+            } => {
+                let mut options = PutOptions::new();
+                if let Some(lease) = lease {
+                    options = options.with_lease(*lease);
+                }
+                if *prev_kv {
+                    options = options.with_prev_key();
+                }
+                TxnOp::put(
+                    key_encoding.decode(key)?,
+                    value_encoding.decode(value)?,
+                    Some(options),
+                )
+            }
+            TxnRequestOp::Delete {
+                key,
+                key_encoding,
+                prefix,
+                prev_kv,
+            } => {
+                let key = key_encoding.decode(key)?;
+                let mut options = DeleteOptions::new();
+                if *prefix {
+                    options = options.with_range(range_end_of_prefix(&key));
+                }
+                if *prev_kv {
+                    options = options.with_prev_key();
+                }
+                TxnOp::delete(key, Some(options))
+            }
+            TxnRequestOp::Get {
+                key,
+                key_encoding,
+                prefix,
+            } => {
+                let key = key_encoding.decode(key)?;
+                let options =
+                    prefix.then(|| GetOptions::new().with_range(range_end_of_prefix(&key)));
+                TxnOp::get(key, options)
+            }
+        })
+    }
+}
+
+impl From<TxnResponse> for TxnResult {
+    fn from(response: TxnResponse) -> Self {
+        TxnResult {
+            succeeded: response.succeeded(),
+            revision: response.header().map_or(0, |h| h.revision()),
+            responses: response
+                .op_responses()
+                .into_iter()
+                .filter_map(|op| match op {
+                    TxnOpResponse::Put(mut put) => Some(TxnOpResult::Put {
+                        prev_kv: put.take_prev_key().map(Item::from),
+                    }),
+                    TxnOpResponse::Delete(delete) => Some(TxnOpResult::Delete {
+                        deleted: delete.deleted(),
+                        prev_kvs: delete.prev_kvs().iter().cloned().map(Item::from).collect(),
+                    }),
+                    TxnOpResponse::Get(mut get) => Some(TxnOpResult::Get {
+                        count: get.count(),
+                        kvs: get.take_kvs().into_iter().map(Item::from).collect(),
+                    }),
+                    // Nested transactions are never built from a `TxnRequest`
+                    TxnOpResponse::Txn(_) => None,
+                })
+                .collect(),
+        }
+    }
+}
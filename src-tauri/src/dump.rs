@@ -0,0 +1,567 @@
+use std::io::{self, BufRead, Write};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use etcd_client::KeyValue;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{Encoding, Item};
+
+/// File formats for exported keys
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DumpFormat {
+    // Items with their metadata, see `client::Item`
+    Json,
+    // Flat `key: value` mapping, binary keys and values tagged `!!binary`
+    Yaml,
+    // Same shape as `etcdctl get -w json`
+    Etcdctl,
+}
+
+/// Where and when a dump was taken
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DumpHeader {
+    pub cluster_id: u64,
+    pub member_id: u64,
+    pub revision: i64,
+    pub raft_term: u64,
+}
+
+impl From<&etcd_client::ResponseHeader> for DumpHeader {
+    fn from(header: &etcd_client::ResponseHeader) -> Self {
+        DumpHeader {
+            cluster_id: header.cluster_id(),
+            member_id: header.member_id(),
+            revision: header.revision(),
+            raft_term: header.raft_term(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonMetadata<'a> {
+    exported_at: String,
+    source: &'a str,
+    #[serde(flatten)]
+    header: &'a DumpHeader,
+}
+
+/// A KV pair as printed by `etcdctl get -w json`, zero values are omitted
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EtcdctlKv {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub create_revision: i64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub mod_revision: i64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub version: i64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub value: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub lease: i64,
+}
+
+fn is_zero(n: &i64) -> bool {
+    *n == 0
+}
+
+/// The fields of a KV pair that are written to a dump
+#[derive(Debug, Default)]
+struct DumpRecord<'a> {
+    key: &'a [u8],
+    value: &'a [u8],
+    create_revision: i64,
+    mod_revision: i64,
+    version: i64,
+    lease: i64,
+}
+
+impl<'a> From<&'a KeyValue> for DumpRecord<'a> {
+    fn from(kv: &'a KeyValue) -> Self {
+        DumpRecord {
+            key: kv.key(),
+            value: kv.value(),
+            create_revision: kv.create_revision(),
+            mod_revision: kv.mod_revision(),
+            version: kv.version(),
+            lease: kv.lease(),
+        }
+    }
+}
+
+impl From<&DumpRecord<'_>> for Item {
+    fn from(record: &DumpRecord) -> Self {
+        let (key, key_encoding) = Encoding::encode_auto(record.key.to_vec());
+        let (value, value_encoding) = Encoding::encode_auto(record.value.to_vec());
+        Item {
+            key,
+            value,
+            key_encoding,
+            value_encoding,
+            version: record.version,
+            create_revision: record.create_revision,
+            mod_revision: record.mod_revision,
+            lease: record.lease,
+        }
+    }
+}
+
+impl From<&DumpRecord<'_>> for EtcdctlKv {
+    fn from(record: &DumpRecord) -> Self {
+        EtcdctlKv {
+            key: BASE64_STANDARD.encode(record.key),
+            create_revision: record.create_revision,
+            mod_revision: record.mod_revision,
+            version: record.version,
+            value: BASE64_STANDARD.encode(record.value),
+            lease: record.lease,
+        }
+    }
+}
+
+/// Writes KV pairs to a dump one at a time, so a whole range never sits in memory
+pub struct DumpWriter<W: Write> {
+    format: DumpFormat,
+    out: W,
+    count: u64,
+}
+
+impl<W: Write> DumpWriter<W> {
+    pub fn begin(
+        format: DumpFormat,
+        mut out: W,
+        header: &DumpHeader,
+        source: &str,
+    ) -> io::Result<Self> {
+        match format {
+            DumpFormat::Json => {
+                let metadata = JsonMetadata {
+                    exported_at: chrono::Utc::now().to_rfc3339(),
+                    source,
+                    header,
+                };
+                out.write_all(b"{\n  \"metadata\": ")?;
+                serde_json::to_writer(&mut out, &metadata)?;
+                out.write_all(b",\n  \"items\": [")?;
+            }
+            DumpFormat::Yaml => {
+                writeln!(out, "# Exported from {source}")?;
+                writeln!(
+                    out,
+                    "# cluster_id: {}, revision: {}",
+                    header.cluster_id, header.revision
+                )?;
+            }
+            DumpFormat::Etcdctl => {
+                out.write_all(b"{\"header\":")?;
+                serde_json::to_writer(&mut out, header)?;
+                out.write_all(b",\"kvs\":[")?;
+            }
+        }
+
+        Ok(DumpWriter {
+            format,
+            out,
+            count: 0,
+        })
+    }
+
+    pub fn write(&mut self, kv: &KeyValue) -> io::Result<()> {
+        self.write_record(&DumpRecord::from(kv))
+    }
+
+    fn write_record(&mut self, record: &DumpRecord) -> io::Result<()> {
+        match self.format {
+            DumpFormat::Json => {
+                let separator: &[u8] = if self.count == 0 {
+                    b"\n    "
+                } else {
+                    b",\n    "
+                };
+                self.out.write_all(separator)?;
+                serde_json::to_writer(&mut self.out, &Item::from(record))?;
+            }
+            DumpFormat::Yaml => {
+                writeln!(
+                    self.out,
+                    "{}: {}",
+                    yaml_scalar(record.key),
+                    yaml_scalar(record.value)
+                )?;
+            }
+            DumpFormat::Etcdctl => {
+                if self.count > 0 {
+                    self.out.write_all(b",\n")?;
+                }
+                serde_json::to_writer(&mut self.out, &EtcdctlKv::from(record))?;
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Close the document, returns the number of KV pairs written
+    pub fn finish(mut self) -> io::Result<u64> {
+        match self.format {
+            DumpFormat::Json => self.out.write_all(b"\n  ]\n}\n")?,
+            DumpFormat::Yaml => {}
+            DumpFormat::Etcdctl => write!(self.out, "],\"count\":{}}}", self.count)?,
+        }
+        self.out.flush()?;
+        Ok(self.count)
+    }
+}
+
+/// Double-quoted scalars are valid in both JSON and YAML, so reuse the JSON escaping
+fn yaml_scalar(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => serde_json::Value::from(text).to_string(),
+        Err(_) => format!(
+            "!!binary {}",
+            serde_json::Value::from(BASE64_STANDARD.encode(bytes))
+        ),
+    }
+}
+
+/// A KV pair read back from a dump
+#[derive(Debug, Clone)]
+pub struct DumpEntry {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct JsonDump {
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct EtcdctlDump {
+    // `etcdctl` omits `kvs` altogether when the range is empty
+    #[serde(default)]
+    kvs: Vec<EtcdctlKv>,
+}
+
+/// Parse a dump written by [`DumpWriter`] or by `etcdctl get -w json`
+pub fn read_dump(format: DumpFormat, input: impl BufRead) -> Result<Vec<DumpEntry>, String> {
+    match format {
+        DumpFormat::Json => {
+            let dump: JsonDump =
+                serde_json::from_reader(input).map_err(|e| format!("Invalid JSON dump: {e}"))?;
+            dump.items
+                .into_iter()
+                .map(|item| {
+                    Ok(DumpEntry {
+                        key: item.key_encoding.decode(&item.key)?,
+                        value: item.value_encoding.decode(&item.value)?,
+                    })
+                })
+                .collect()
+        }
+        DumpFormat::Etcdctl => {
+            let dump: EtcdctlDump =
+                serde_json::from_reader(input).map_err(|e| format!("Invalid etcdctl dump: {e}"))?;
+            dump.kvs
+                .into_iter()
+                .map(|kv| {
+                    Ok(DumpEntry {
+                        key: Encoding::Base64.decode(&kv.key)?,
+                        value: Encoding::Base64.decode(&kv.value)?,
+                    })
+                })
+                .collect()
+        }
+        DumpFormat::Yaml => {
+            let mut entries = Vec::new();
+            for (i, line) in input.lines().enumerate() {
+                let line = line.map_err(|e| e.to_string())?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // Indentation would make the line part of a nested mapping or a multi-line
+                // scalar, neither of which maps to a single key
+                if line.starts_with([' ', '\t']) {
+                    return Err(format!(
+                        "Line {}: Indented lines are not supported, expected a flat mapping",
+                        i + 1
+                    ));
+                }
+                let line = line.trim_end();
+                if line.starts_with('#') || line == "---" {
+                    continue;
+                }
+                let entry = parse_yaml_line(line).map_err(|e| format!("Line {}: {e}", i + 1))?;
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+}
+
+/// Parse a `key: value` line of a flat YAML mapping.
+///
+/// Only the scalars written by [`yaml_scalar`] are fully supported, plus plain and
+/// single-quoted ones for hand-written files. Anything YAML would read as more than
+/// a single string is rejected rather than imported as something else.
+fn parse_yaml_line(line: &str) -> Result<DumpEntry, String> {
+    let (key, rest) = if line.starts_with(['"', '\'', '!']) {
+        let (key, rest) = parse_yaml_scalar(line)?;
+        let rest = rest
+            .trim_start()
+            .strip_prefix(':')
+            .ok_or("Expected ':' after the key")?;
+        (key, rest)
+    } else {
+        let (key, rest) = line
+            .split_once(": ")
+            .or_else(|| line.strip_suffix(':').map(|key| (key, "")))
+            .ok_or("Expected a 'key: value' pair")?;
+        let key = key.trim_end();
+        check_plain_scalar(key)?;
+        (key.as_bytes().to_vec(), rest)
+    };
+
+    let rest = rest.trim();
+    let value = if rest.starts_with(['"', '\'', '!']) {
+        let (value, trailing) = parse_yaml_scalar(rest)?;
+        let trailing = trailing.trim_start();
+        if !trailing.is_empty() && !trailing.starts_with('#') {
+            return Err(format!("Unexpected '{trailing}' after the value"));
+        }
+        value
+    } else {
+        check_plain_scalar(rest)?;
+        rest.as_bytes().to_vec()
+    };
+
+    Ok(DumpEntry { key, value })
+}
+
+/// Reject plain scalars that YAML would not read as the string they spell out
+fn check_plain_scalar(text: &str) -> Result<(), String> {
+    if text.starts_with(['&', '*']) {
+        return Err(format!("Anchors and aliases are not supported: '{text}'"));
+    }
+    if text.starts_with(['|', '>']) {
+        return Err(format!("Multi-line scalars are not supported: '{text}'"));
+    }
+    if text.starts_with(['[', '{', '?']) || text == "-" || text.starts_with("- ") {
+        return Err(format!(
+            "Only a flat mapping of scalars is supported: '{text}'"
+        ));
+    }
+    if text.starts_with(['%', '@', '`', ',', ']', '}']) {
+        return Err(format!(
+            "Reserved character at the start of '{text}', quote it"
+        ));
+    }
+    if text.contains(" #") || text.contains("\t#") {
+        return Err(format!(
+            "Comments after a plain scalar are not supported, quote it: '{text}'"
+        ));
+    }
+    if text.contains(": ") || text.ends_with(':') {
+        return Err(format!("Nested mappings are not supported: '{text}'"));
+    }
+    Ok(())
+}
+
+/// Parse a quoted or `!!binary` scalar, returns its bytes and the rest of the input
+fn parse_yaml_scalar(input: &str) -> Result<(Vec<u8>, &str), String> {
+    if let Some(rest) = input.strip_prefix("!!binary") {
+        let (text, rest) = parse_yaml_scalar(rest.trim_start())?;
+        let text = String::from_utf8(text).map_err(|e| e.to_string())?;
+        return Ok((Encoding::Base64.decode(&text)?, rest));
+    }
+    if input.starts_with('!') {
+        return Err("Only the !!binary tag is supported".to_string());
+    }
+
+    if let Some(rest) = input.strip_prefix('\'') {
+        // A quote inside a single-quoted scalar is escaped by doubling it
+        let mut text = String::new();
+        let mut chars = rest.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c != '\'' {
+                text.push(c);
+            } else if chars.peek().is_some_and(|(_, next)| *next == '\'') {
+                text.push('\'');
+                chars.next();
+            } else {
+                return Ok((text.into_bytes(), &rest[i + 1..]));
+            }
+        }
+        return Err("Unterminated single-quoted scalar".to_string());
+    }
+
+    // Double-quoted scalars are written with JSON escaping
+    let mut stream = serde_json::Deserializer::from_str(input).into_iter::<String>();
+    let text = stream
+        .next()
+        .ok_or("Expected a scalar")?
+        .map_err(|e| format!("Invalid double-quoted scalar: {e}"))?;
+    Ok((text.into_bytes(), &input[stream.byte_offset()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_dump(format: DumpFormat, pairs: &[(&[u8], &[u8])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut writer =
+            DumpWriter::begin(format, &mut out, &DumpHeader::default(), "test").unwrap();
+        for (key, value) in pairs {
+            let record = DumpRecord {
+                key,
+                value,
+                create_revision: 2,
+                mod_revision: 3,
+                version: 1,
+                ..Default::default()
+            };
+            writer.write_record(&record).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), pairs.len() as u64);
+        out
+    }
+
+    fn round_trip(format: DumpFormat, pairs: &[(&[u8], &[u8])]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        read_dump(format, write_dump(format, pairs).as_slice())
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect()
+    }
+
+    const PAIRS: &[(&[u8], &[u8])] = &[
+        (b"/app/plain", b"value"),
+        (b"/app/empty", b""),
+        (b"/app/quotes", b"say \"hi\": 'there' # not a comment"),
+        (b"/app/multiline", b"line 1\nline 2\ttabbed"),
+        (b"/app/unicode", "h\u{e9}llo \u{1f600}".as_bytes()),
+        (b"/app/\xff\xfe", b"\x00\x01\x80binary"),
+    ];
+
+    #[test]
+    fn round_trips_every_format() {
+        for format in [DumpFormat::Json, DumpFormat::Yaml, DumpFormat::Etcdctl] {
+            let expected: Vec<_> = PAIRS
+                .iter()
+                .map(|(k, v)| (k.to_vec(), v.to_vec()))
+                .collect();
+            assert_eq!(round_trip(format, PAIRS), expected, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn round_trips_empty_dumps() {
+        for format in [DumpFormat::Json, DumpFormat::Yaml, DumpFormat::Etcdctl] {
+            assert!(round_trip(format, &[]).is_empty(), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn writes_valid_json_documents() {
+        let pairs: &[(&[u8], &[u8])] = &[(b"/app/a", b"1"), (b"/app/\xff", b"\x00")];
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&write_dump(DumpFormat::Json, pairs)).unwrap();
+        assert_eq!(json["items"].as_array().map(Vec::len), Some(2));
+        assert_eq!(json["items"][0]["key"], "/app/a");
+        assert_eq!(json["items"][1]["key_encoding"], "base64");
+
+        let etcdctl: serde_json::Value =
+            serde_json::from_slice(&write_dump(DumpFormat::Etcdctl, pairs)).unwrap();
+        assert_eq!(etcdctl["count"], 2);
+        assert_eq!(etcdctl["kvs"][0]["key"], BASE64_STANDARD.encode("/app/a"));
+        assert_eq!(etcdctl["kvs"][0]["version"], 1);
+
+        let empty: serde_json::Value =
+            serde_json::from_slice(&write_dump(DumpFormat::Json, &[])).unwrap();
+        assert_eq!(empty["items"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn yaml_binary_is_tagged() {
+        assert_eq!(yaml_scalar(b"text"), "\"text\"");
+        assert_eq!(yaml_scalar(b"\xff\x00"), "!!binary \"/wA=\"");
+    }
+
+    #[test]
+    fn reads_etcdctl_output_without_kvs() {
+        let output = r#"{"header":{"cluster_id":1,"member_id":2,"revision":3,"raft_term":4}}"#;
+        assert!(
+            read_dump(DumpFormat::Etcdctl, output.as_bytes())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    fn parse(line: &str) -> (Vec<u8>, Vec<u8>) {
+        let entry = parse_yaml_line(line).unwrap();
+        (entry.key, entry.value)
+    }
+
+    #[test]
+    fn parses_yaml_scalars() {
+        assert_eq!(parse("key: value"), (b"key".to_vec(), b"value".to_vec()));
+        assert_eq!(parse("key:"), (b"key".to_vec(), Vec::new()));
+        assert_eq!(
+            parse(r#""a: b": "c\nd""#),
+            (b"a: b".to_vec(), b"c\nd".to_vec())
+        );
+        assert_eq!(
+            parse("'it''s': 'x' # comment"),
+            (b"it's".to_vec(), b"x".to_vec())
+        );
+        assert_eq!(
+            parse(r#"!!binary "/wA=": !!binary "AAE=""#),
+            (vec![0xff, 0x00], vec![0x00, 0x01])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_yaml_lines() {
+        assert!(parse_yaml_line("no separator").is_err());
+        assert!(parse_yaml_line("'unterminated: value").is_err());
+        assert!(parse_yaml_line(r#""key" value"#).is_err());
+        assert!(parse_yaml_line(r#"key: "value" trailing"#).is_err());
+        assert!(parse_yaml_line(r#"key: !!binary "not base64!""#).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_yaml() {
+        assert!(parse_yaml_line("key: value # comment").is_err());
+        assert!(parse_yaml_line("key: &anchor value").is_err());
+        assert!(parse_yaml_line("key: *alias").is_err());
+        assert!(parse_yaml_line("key: |").is_err());
+        assert!(parse_yaml_line("key: [a, b]").is_err());
+        assert!(parse_yaml_line("- key: value").is_err());
+        assert!(parse_yaml_line("key: a: b").is_err());
+        assert!(parse_yaml_line("key: !custom value").is_err());
+    }
+
+    #[test]
+    fn yaml_rejects_nested_mappings_with_their_line() {
+        let input = "# header\na:\n  b: 1\n";
+        let err = read_dump(DumpFormat::Yaml, input.as_bytes()).unwrap_err();
+        assert!(err.starts_with("Line 3:"), "{err}");
+    }
+
+    #[test]
+    fn yaml_skips_comments_and_separators() {
+        let input = "# header\n---\n\n\"a\": \"1\"\nb: 2  \n";
+        let entries = read_dump(DumpFormat::Yaml, input.as_bytes()).unwrap();
+        let pairs: Vec<_> = entries.into_iter().map(|e| (e.key, e.value)).collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec())
+            ]
+        );
+    }
+}
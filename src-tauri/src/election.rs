@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use etcd_client::ObserveStream;
+use serde::Serialize;
+use tauri::Emitter;
+use tauri::async_runtime::JoinHandle;
+
+use crate::client::Item;
+
+pub const ELECTION_LEADER_EVENT: &str = "election-leader";
+
+/// Payload emitted on [`ELECTION_LEADER_EVENT`] whenever an election's leader changes
+#[derive(Serialize, Clone, Debug)]
+pub struct ElectionLeaderEvent {
+    pub observe_id: u64,
+    pub leader: Option<Item>,
+    // Last event of an observation that ended on its own, failed if `error` is set.
+    // Observers stopped on request are aborted and get no event
+    pub ended: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Observer ID -> (profile, forwarding task)
+type ObserverTasks = Arc<Mutex<HashMap<u64, (String, JoinHandle<()>)>>>;
+
+/// Elections observed by the UI, keyed by an ID local to this app
+#[derive(Default)]
+pub struct Observers {
+    next_id: u64,
+    // Shared with the forwarding tasks, which remove their observer when its stream ends
+    tasks: ObserverTasks,
+}
+
+impl Observers {
+    /// Forward the leaders of `stream` to the UI until stopped.
+    ///
+    /// Returns the ID used in the emitted [`ElectionLeaderEvent`]s.
+    pub fn spawn(
+        &mut self,
+        app_handle: tauri::AppHandle,
+        profile: String,
+        stream: ObserveStream,
+    ) -> u64 {
+        self.next_id += 1;
+        let observe_id = self.next_id;
+        // Hold the lock until the task is in, in case the stream ends right away
+        let mut tasks = lock(&self.tasks);
+        let task = tauri::async_runtime::spawn(forward_leaders(
+            app_handle,
+            observe_id,
+            stream,
+            self.tasks.clone(),
+        ));
+        tasks.insert(observe_id, (profile, task));
+        observe_id
+    }
+
+    pub fn stop(&mut self, observe_id: u64) -> Result<(), String> {
+        lock(&self.tasks)
+            .remove(&observe_id)
+            .map(|(_, task)| task.abort())
+            .ok_or_else(|| format!("Election observer {observe_id} not found"))
+    }
+
+    pub fn clear_profile(&mut self, profile: &str) {
+        lock(&self.tasks).retain(|observe_id, (task_profile, task)| {
+            if task_profile != profile {
+                return true;
+            }
+            log::debug!("Stopping election observer {observe_id}");
+            task.abort();
+            false
+        });
+    }
+}
+
+fn lock(tasks: &ObserverTasks) -> MutexGuard<'_, HashMap<u64, (String, JoinHandle<()>)>> {
+    tasks.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn emit_leader_event(app_handle: &tauri::AppHandle, payload: ElectionLeaderEvent) {
+    if let Err(err) = app_handle.emit(ELECTION_LEADER_EVENT, payload) {
+        log::error!("Failed to emit election leader event: {err}");
+    }
+}
+
+async fn forward_leaders(
+    app_handle: tauri::AppHandle,
+    observe_id: u64,
+    stream: ObserveStream,
+    tasks: ObserverTasks,
+) {
+    let error = forward_stream(&app_handle, observe_id, stream).await;
+    // Observers stopped on request are aborted before getting here, so this one ended
+    // on its own: forget it and let the UI know
+    lock(&tasks).remove(&observe_id);
+    log::info!("Election observer {observe_id} ended");
+    emit_leader_event(
+        &app_handle,
+        ElectionLeaderEvent {
+            observe_id,
+            leader: None,
+            ended: true,
+            error,
+        },
+    );
+}
+
+/// Emit the leaders of `stream` until it ends, returns the error it failed with if any
+async fn forward_stream(
+    app_handle: &tauri::AppHandle,
+    observe_id: u64,
+    mut stream: ObserveStream,
+) -> Option<String> {
+    loop {
+        match stream.message().await {
+            Ok(Some(response)) => emit_leader_event(
+                app_handle,
+                ElectionLeaderEvent {
+                    observe_id,
+                    leader: response.kv().cloned().map(Item::from),
+                    ended: false,
+                    error: None,
+                },
+            ),
+            Ok(None) => return None,
+            Err(e) => {
+                log::error!("Election observer {observe_id} failed: {e}");
+                return Some(e.to_string());
+            }
+        }
+    }
+}
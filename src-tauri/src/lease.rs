@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use etcd_client::{LeaseKeepAliveStream, LeaseKeeper};
+use serde::Serialize;
+use tauri::Emitter;
+use tauri::async_runtime::JoinHandle;
+
+use crate::client::ItemKey;
+
+pub const LEASE_KEEP_ALIVE_EVENT: &str = "lease-keep-alive";
+
+/// Details of a single lease
+#[derive(Serialize, Debug)]
+pub struct LeaseInfo {
+    pub id: i64,
+    // Remaining TTL in seconds, -1 if the lease has expired
+    pub ttl: i64,
+    pub granted_ttl: i64,
+    pub keys: Vec<ItemKey>,
+}
+
+impl From<etcd_client::LeaseTimeToLiveResponse> for LeaseInfo {
+    fn from(response: etcd_client::LeaseTimeToLiveResponse) -> Self {
+        LeaseInfo {
+            id: response.id(),
+            ttl: response.ttl(),
+            granted_ttl: response.granted_ttl(),
+            keys: response
+                .keys()
+                .iter()
+                .map(|key| ItemKey::from(key.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LeaseKeepAliveEvent {
+    // Profile of the cluster the lease belongs to, lease IDs are only unique per cluster
+    pub profile: String,
+    pub lease_id: i64,
+    // TTL returned by the last keep-alive, 0 once the lease is gone
+    pub ttl: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// (profile, lease ID) -> refresh task
+type KeepAliveTasks = Arc<Mutex<HashMap<(String, i64), JoinHandle<()>>>>;
+
+/// Leases kept alive in the background, keyed by profile and lease ID
+#[derive(Default)]
+pub struct KeepAlives {
+    // Shared with the tasks, which remove their lease when it can no longer be kept alive
+    tasks: KeepAliveTasks,
+}
+
+impl KeepAlives {
+    /// Refresh the lease periodically until it is stopped or expires
+    pub fn spawn(
+        &mut self,
+        app_handle: tauri::AppHandle,
+        profile: String,
+        keeper: LeaseKeeper,
+        stream: LeaseKeepAliveStream,
+    ) {
+        let lease_id = keeper.id();
+        // Hold the lock until the task is in, in case the lease is already gone
+        let mut tasks = lock(&self.tasks);
+        let task = tauri::async_runtime::spawn(keep_alive(
+            app_handle,
+            profile.clone(),
+            keeper,
+            stream,
+            self.tasks.clone(),
+        ));
+        if let Some(previous) = tasks.insert((profile, lease_id), task) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&mut self, profile: &str, lease_id: i64) -> Result<(), String> {
+        lock(&self.tasks)
+            .remove(&(profile.to_string(), lease_id))
+            .map(|task| task.abort())
+            .ok_or_else(|| format!("Lease {lease_id} is not being kept alive"))
+    }
+
+    pub fn clear_profile(&mut self, profile: &str) {
+        lock(&self.tasks).retain(|(task_profile, lease_id), task| {
+            if task_profile != profile {
+                return true;
+            }
+            log::debug!("Stopping keep-alive of lease {lease_id}");
+            task.abort();
+            false
+        });
+    }
+}
+
+fn lock(tasks: &KeepAliveTasks) -> MutexGuard<'_, HashMap<(String, i64), JoinHandle<()>>> {
+    tasks.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn emit_keep_alive_event(app_handle: &tauri::AppHandle, payload: LeaseKeepAliveEvent) {
+    if let Err(err) = app_handle.emit(LEASE_KEEP_ALIVE_EVENT, payload) {
+        log::error!("Failed to emit lease keep-alive event: {err}");
+    }
+}
+
+async fn keep_alive(
+    app_handle: tauri::AppHandle,
+    profile: String,
+    keeper: LeaseKeeper,
+    stream: LeaseKeepAliveStream,
+    tasks: KeepAliveTasks,
+) {
+    let lease_id = keeper.id();
+    refresh_lease(&app_handle, &profile, keeper, stream).await;
+    // Keep-alives stopped on request are aborted before getting here, so this one ended
+    // on its own: forget it
+    lock(&tasks).remove(&(profile, lease_id));
+}
+
+/// Refresh the lease until it expires or a keep-alive fails, emitting every new TTL
+async fn refresh_lease(
+    app_handle: &tauri::AppHandle,
+    profile: &str,
+    mut keeper: LeaseKeeper,
+    mut stream: LeaseKeepAliveStream,
+) {
+    let lease_id = keeper.id();
+    loop {
+        let res = match keeper.keep_alive().await {
+            Ok(()) => stream.message().await,
+            Err(e) => Err(e),
+        };
+
+        let ttl = match res {
+            Ok(Some(response)) => response.ttl(),
+            Ok(None) => 0,
+            Err(e) => {
+                log::error!("Keep-alive of lease {lease_id} failed: {e}");
+                emit_keep_alive_event(
+                    app_handle,
+                    LeaseKeepAliveEvent {
+                        profile: profile.to_string(),
+                        lease_id,
+                        ttl: 0,
+                        error: Some(e.to_string()),
+                    },
+                );
+                return;
+            }
+        };
+
+        emit_keep_alive_event(
+            app_handle,
+            LeaseKeepAliveEvent {
+                profile: profile.to_string(),
+                lease_id,
+                ttl,
+                error: None,
+            },
+        );
+        if ttl <= 0 {
+            log::info!("Lease {lease_id} has expired, stopping keep-alive");
+            return;
+        }
+
+        // Refresh well before the lease runs out, like etcdctl does
+        tokio::time::sleep(Duration::from_secs((ttl as u64 / 3).max(1))).await;
+    }
+}
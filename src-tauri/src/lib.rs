@@ -1,13 +1,19 @@
 mod client;
 mod config;
 mod core;
+mod dump;
+mod election;
+mod lease;
 mod metrics;
+mod pool;
+mod secrets;
 mod state;
 mod update;
+mod watch;
 
 use chrono::{DateTime, Local, TimeZone, Utc};
-use serde::Serialize;
-use state::AppState;
+use serde::{Deserialize, Serialize};
+use state::{AppState, Session};
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
@@ -16,11 +22,13 @@ use std::sync::Arc;
 use tauri::webview::cookie::time::format_description::well_known::Rfc3339;
 use tauri::{Emitter, Manager, State};
 use tauri_plugin_log::{Target, TargetKind, TimezoneStrategy};
-use tokio::sync::{Mutex, Notify};
+use tokio::sync::Notify;
 
 use crate::metrics::{fetch_metrics_text, parse_metrics_text};
 
 const UPDATE_CHECK_EVENT: &str = "update-check";
+const IMPORT_PROGRESS_EVENT: &str = "import-progress";
+const SNAPSHOT_PROGRESS_EVENT: &str = "snapshot-progress";
 
 #[derive(Clone, Default)]
 struct UpdateCheckWorkerControl {
@@ -96,11 +104,10 @@ async fn update_check_worker(
 
     loop {
         let (channel, schedule) = {
-            let state = app_handle.state::<Mutex<AppState>>();
-            let app_state = state.lock().await;
+            let config = app_handle.state::<AppState>().config();
             (
-                app_state.app_config.update_channel.clone(),
-                app_state.app_config.update_check_schedule.clone(),
+                config.update_channel.clone(),
+                config.update_check_schedule.clone(),
             )
         };
 
@@ -153,12 +160,9 @@ async fn check_update(
 #[tauri::command]
 async fn trigger_update_check(
     app_handle: tauri::AppHandle,
-    state: State<'_, Mutex<AppState>>,
+    state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let channel = {
-        let app_state = state.lock().await;
-        app_state.app_config.update_channel.clone()
-    };
+    let channel = state.config().update_channel.clone();
 
     run_update_check_and_emit(&app_handle, channel, UpdateCheckTrigger::Manual).await;
     Ok(())
@@ -190,19 +194,29 @@ fn format_timestamp(timestamp_ms: i64) -> Result<FormattedTimestamp, String> {
     Ok(FormattedTimestamp { utc, local })
 }
 
-/// Initialize the etcd client managed by the application state.
+/// Initialize the etcd client of a profile, the current one by default.
 ///
+/// A pooled client is reused unless ```reconnect``` is set.
 /// Returns false if the ```current_profile``` is not pointinng to a valid profile,
 /// otherwise returns true.
 #[tauri::command]
-async fn initialize_etcd_client(state: State<'_, Mutex<AppState>>) -> Result<bool, String> {
+async fn initialize_etcd_client(
+    profile: Option<String>,
+    reconnect: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
     log::info!("Initializing etcd client...");
-    let _ = state.lock().await.etcd_client.take(); // Reset the client if it exists
-    state
-        .lock()
-        .await
-        .init_client()
+    if profile.is_none() && state.config().get_current_profile().is_none() {
+        return Ok(false);
+    }
+    let session = state.session(profile)?;
+    if reconnect.unwrap_or(false) {
+        session.reset_client();
+    }
+    session
+        .client()
         .await
+        .map(|_| true)
         .inspect(|_| log::info!("Etcd client initialized successfully"))
         .inspect_err(|e| log::error!("Failed to initialize client: {}", e))
 }
@@ -210,12 +224,13 @@ async fn initialize_etcd_client(state: State<'_, Mutex<AppState>>) -> Result<boo
 #[tauri::command]
 async fn list_items(
     prefix: String,
-    state: State<'_, Mutex<AppState>>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
 ) -> Result<Vec<client::Item>, String> {
     log::debug!("Listing keys with prefix: {}", prefix);
     // Call the client function with the provided prefix
-    let mut state = state.lock().await;
-    core::list_items(&prefix, &mut state)
+    let session = state.session(profile)?;
+    core::list_items(&prefix, &session)
         .await
         .inspect_err(|e| log::error!("Failed to list keys: {}", e))
 }
@@ -223,11 +238,12 @@ async fn list_items(
 #[tauri::command]
 async fn list_keys_only(
     prefix: String,
-    state: State<'_, Mutex<AppState>>,
-) -> Result<Vec<String>, String> {
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<client::ItemKey>, String> {
     log::debug!("Listing keys only with prefix: {}", prefix);
-    let mut state = state.lock().await;
-    core::list_keys_only(&prefix, &mut state)
+    let session = state.session(profile)?;
+    core::list_keys_only(&prefix, &session)
         .await
         .inspect(|v| log::info!("Found {} keys with prefix {}", v.len(), prefix))
         .inspect_err(|e| log::error!("Failed to list keys only: {}", e))
@@ -237,83 +253,1039 @@ async fn list_keys_only(
 async fn get_values_in_range(
     start_key: String,
     end_inclusive: String,
-    state: State<'_, Mutex<AppState>>,
+    start_key_encoding: Option<client::Encoding>,
+    end_key_encoding: Option<client::Encoding>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
 ) -> Result<Vec<client::Item>, String> {
     log::debug!("Getting values in range: {} ~ {}", start_key, end_inclusive);
-    let mut state = state.lock().await;
-    core::get_values_in_range(&start_key, &end_inclusive, &mut state)
+    let start_key = start_key_encoding.unwrap_or_default().decode(&start_key)?;
+    let end_inclusive = end_key_encoding
+        .unwrap_or_default()
+        .decode(&end_inclusive)?;
+    let session = state.session(profile)?;
+    core::get_values_in_range(&start_key, &end_inclusive, &session)
         .await
         .inspect_err(|e| log::error!("Failed to get values in range: {}", e))
 }
 
+/// Optional parameters of ```put_key```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PutKeyOptions {
+    key_encoding: client::Encoding,
+    value_encoding: client::Encoding,
+    // Attach the key to an existing lease
+    lease_id: Option<i64>,
+    // Attach the key to a new lease with this TTL in seconds
+    ttl: Option<i64>,
+    // Only write if the key is still at this revision, 0 if it must not exist
+    expected_mod_revision: Option<i64>,
+    // Write unconditionally, required when `expected_mod_revision` is not set
+    overwrite: bool,
+}
+
 #[tauri::command]
 async fn put_key(
     key: String,
     value: String,
-    state: State<'_, Mutex<AppState>>,
-) -> Result<(), String> {
+    options: Option<PutKeyOptions>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), core::WriteError> {
     log::info!("Putting key: {}", key);
-    let mut state = state.lock().await;
-    state.app_config.ensure_current_profile_unlocked()?;
-    core::put_key(&key, &value, &mut state)
+    let options = options.unwrap_or_default();
+    if options.expected_mod_revision.is_none() && !options.overwrite {
+        return Err(
+            "Refusing to write without the expected mod_revision, set overwrite to replace the key unconditionally"
+                .to_string()
+                .into(),
+        );
+    }
+    let key_bytes = options.key_encoding.decode(&key)?;
+    let value_bytes = options.value_encoding.decode(&value)?;
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    // Lease granted for this write only, to revoke if the write does not happen
+    let mut granted_lease = None;
+    let lease_id = match (options.lease_id, options.ttl) {
+        (Some(_), Some(_)) => {
+            return Err("Specify either a lease ID or a TTL, not both"
+                .to_string()
+                .into());
+        }
+        (None, Some(ttl)) => {
+            let id = core::grant_lease(ttl, None, &session)
+                .await
+                .inspect_err(|e| log::error!("Failed to grant lease for key {}: {}", key, e))?;
+            granted_lease = Some(id);
+            Some(id)
+        }
+        (lease_id, None) => lease_id,
+    };
+    let res = core::put_key(
+        &key_bytes,
+        &value_bytes,
+        lease_id,
+        options.expected_mod_revision,
+        &session,
+    )
+    .await
+    .inspect_err(|e| log::error!("Failed to put key {}: {:?}", key, e));
+
+    if let (Err(_), Some(id)) = (&res, granted_lease) {
+        let _ = core::revoke_lease(id, &session)
+            .await
+            .inspect_err(|e| log::warn!("Failed to revoke unused lease {}: {}", id, e));
+    }
+    res
+}
+
+#[tauri::command]
+async fn execute_txn(
+    request: core::TxnRequest,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::TxnResult, String> {
+    // Values may be secrets, only log the shape of the transaction
+    log::info!(
+        "Executing transaction: {} compares, {} success ops, {} failure ops",
+        request.compare.len(),
+        request.success.len(),
+        request.failure.len()
+    );
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::execute_txn(&request, &session)
         .await
-        .inspect_err(|e| log::error!("Failed to put key {}: {}", key, e))
+        .inspect(|res| log::info!("Transaction succeeded: {}", res.succeeded))
+        .inspect_err(|e| log::error!("Failed to execute transaction: {}", e))
 }
 
 #[tauri::command]
-async fn delete_key(key: String, state: State<'_, Mutex<AppState>>) -> Result<(), String> {
+async fn delete_key(
+    key: String,
+    key_encoding: Option<client::Encoding>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
     log::info!("Deleting key: {}", key);
-    let mut state = state.lock().await;
-    state.app_config.ensure_current_profile_unlocked()?;
-    core::delete_key(&key, &mut state)
+    let key_bytes = key_encoding.unwrap_or_default().decode(&key)?;
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::delete_key(&key_bytes, &session)
         .await
         .inspect_err(|e| log::error!("Failed to delete key {}: {}", key, e))
 }
 
+/// Optional parameters of ```delete_prefix``` and ```delete_range```
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct DeleteRangeOptions {
+    key_encoding: client::Encoding,
+    // Only count the keys and return a sample of them
+    dry_run: bool,
+    // Return the deleted entries
+    prev_kv: bool,
+    sample_size: i64,
+}
+
+impl Default for DeleteRangeOptions {
+    fn default() -> Self {
+        DeleteRangeOptions {
+            key_encoding: client::Encoding::default(),
+            dry_run: false,
+            prev_kv: false,
+            sample_size: 20,
+        }
+    }
+}
+
+#[tauri::command]
+async fn delete_prefix(
+    prefix: String,
+    options: Option<DeleteRangeOptions>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::DeleteRangeResult, String> {
+    let options = options.unwrap_or_default();
+    log::info!("Deleting prefix {} ({:?})", prefix, options);
+    let prefix_bytes = options.key_encoding.decode(&prefix)?;
+    let session = state.session(profile)?;
+    if !options.dry_run {
+        session.ensure_unlocked()?;
+    }
+    core::delete_prefix(
+        &prefix_bytes,
+        options.dry_run,
+        options.prev_kv,
+        options.sample_size,
+        &session,
+    )
+    .await
+    .inspect(|res| {
+        let verb = if res.dry_run {
+            "Would delete"
+        } else {
+            "Deleted"
+        };
+        log::info!("{} {} keys with prefix {}", verb, res.count, prefix)
+    })
+    .inspect_err(|e| log::error!("Failed to delete prefix {}: {}", prefix, e))
+}
+
 #[tauri::command]
-async fn get_cluster_info(state: State<'_, Mutex<AppState>>) -> Result<ClusterInfo, String> {
+async fn delete_range(
+    start_key: String,
+    end_inclusive: String,
+    options: Option<DeleteRangeOptions>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::DeleteRangeResult, String> {
+    let options = options.unwrap_or_default();
+    log::info!(
+        "Deleting range {} ~ {} ({:?})",
+        start_key,
+        end_inclusive,
+        options
+    );
+    let start_bytes = options.key_encoding.decode(&start_key)?;
+    let end_bytes = options.key_encoding.decode(&end_inclusive)?;
+    let session = state.session(profile)?;
+    if !options.dry_run {
+        session.ensure_unlocked()?;
+    }
+    core::delete_range_inclusive(
+        &start_bytes,
+        &end_bytes,
+        options.dry_run,
+        options.prev_kv,
+        options.sample_size,
+        &session,
+    )
+    .await
+    .inspect(|res| {
+        let verb = if res.dry_run {
+            "Would delete"
+        } else {
+            "Deleted"
+        };
+        log::info!("{} {} keys in range", verb, res.count)
+    })
+    .inspect_err(|e| log::error!("Failed to delete range: {}", e))
+}
+
+#[tauri::command]
+async fn export_prefix(
+    prefix: String,
+    path: PathBuf,
+    format: dump::DumpFormat,
+    key_encoding: Option<client::Encoding>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::ExportResult, String> {
+    log::info!(
+        "Exporting prefix {} to {} as {:?}",
+        prefix,
+        path.display(),
+        format
+    );
+    let prefix_bytes = key_encoding.unwrap_or_default().decode(&prefix)?;
+    let session = state.session(profile)?;
+    core::export_prefix(&prefix_bytes, &path, format, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to export prefix {}: {}", prefix, e))
+}
+
+#[tauri::command]
+async fn export_range(
+    start_key: String,
+    end_inclusive: String,
+    path: PathBuf,
+    format: dump::DumpFormat,
+    key_encoding: Option<client::Encoding>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::ExportResult, String> {
+    log::info!(
+        "Exporting range {} ~ {} to {} as {:?}",
+        start_key,
+        end_inclusive,
+        path.display(),
+        format
+    );
+    let key_encoding = key_encoding.unwrap_or_default();
+    let start_bytes = key_encoding.decode(&start_key)?;
+    let end_bytes = key_encoding.decode(&end_inclusive)?;
+    let session = state.session(profile)?;
+    core::export_range_inclusive(&start_bytes, &end_bytes, &path, format, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to export range: {}", e))
+}
+
+/// Optional parameters of ```import_keys```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ImportOptions {
+    policy: core::ConflictPolicy,
+    // Replace this prefix of every imported key with `rewrite_to`
+    rewrite_from: Option<String>,
+    rewrite_to: Option<String>,
+}
+
+fn emit_import_progress_event(app_handle: &tauri::AppHandle, payload: &core::ImportProgress) {
+    if let Err(err) = app_handle.emit(IMPORT_PROGRESS_EVENT, payload) {
+        log::error!("Failed to emit import-progress event: {err}");
+    }
+}
+
+/// Load a dump into the current profile.
+///
+/// Progress is reported with an ```import-progress``` event after every batch.
+#[tauri::command]
+async fn import_keys(
+    path: PathBuf,
+    format: dump::DumpFormat,
+    options: Option<ImportOptions>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<core::ImportProgress, String> {
+    let options = options.unwrap_or_default();
+    log::info!(
+        "Importing {} as {:?} ({:?})",
+        path.display(),
+        format,
+        options
+    );
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+
+    // Reading and parsing the whole file blocks, keep it off the async runtime
+    let reading = path.clone();
+    let mut entries = tokio::task::spawn_blocking(move || {
+        let file = File::open(&reading)
+            .map_err(|e| format!("Failed to open {}: {e}", reading.display()))?;
+        dump::read_dump(format, std::io::BufReader::new(file))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .inspect_err(|e| log::error!("Failed to read {}: {}", path.display(), e))?;
+    if let Some(from) = &options.rewrite_from {
+        let to = options.rewrite_to.as_deref().unwrap_or_default();
+        core::rewrite_prefix(&mut entries, from.as_bytes(), to.as_bytes())?;
+    }
+
+    core::import_entries(
+        &entries,
+        options.policy,
+        |progress| emit_import_progress_event(&app_handle, progress),
+        &session,
+    )
+    .await
+    .inspect(|res| {
+        log::info!(
+            "Imported {} keys, skipped {} existing ones",
+            res.written,
+            res.skipped
+        )
+    })
+    .inspect_err(|e| log::error!("Failed to import {}: {}", path.display(), e))
+}
+
+/// One side of ```diff_prefixes``` and ```sync_prefix```
+#[derive(Deserialize, Debug)]
+struct DiffSide {
+    profile: String,
+    prefix: String,
+    #[serde(default)]
+    key_encoding: client::Encoding,
+}
+
+/// Compare two prefixes, possibly on two different profiles
+#[tauri::command]
+async fn diff_prefixes(
+    left: DiffSide,
+    right: DiffSide,
+    ignore_prefix: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<core::DiffResult, String> {
+    log::info!("Diffing {:?} against {:?}", left, right);
+    let left_session = state.session(Some(left.profile.clone()))?;
+    let right_session = state.session(Some(right.profile.clone()))?;
+    let left_prefix = left.key_encoding.decode(&left.prefix)?;
+    let right_prefix = right.key_encoding.decode(&right.prefix)?;
+
+    core::diff_prefixes(
+        (&left_session, &left_prefix),
+        (&right_session, &right_prefix),
+        ignore_prefix.unwrap_or(true),
+    )
+    .await
+    .inspect(|res| {
+        log::info!(
+            "Diff: {} added, {} removed, {} changed, {} unchanged",
+            res.added.len(),
+            res.removed.len(),
+            res.changed.len(),
+            res.unchanged
+        )
+    })
+    .inspect_err(|e| log::error!("Failed to diff prefixes: {}", e))
+}
+
+/// Optional parameters of ```sync_prefix```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct SyncPrefixOptions {
+    // Only sync these keys, relative to the source prefix and in the source's key encoding
+    keys: Option<Vec<String>>,
+    dry_run: bool,
+    delete_extraneous: bool,
+    preserve_lease: bool,
+}
+
+/// Copy a prefix from one profile to another, only writing keys that differ
+#[tauri::command]
+async fn sync_prefix(
+    source: DiffSide,
+    target: DiffSide,
+    options: Option<SyncPrefixOptions>,
+    state: State<'_, AppState>,
+) -> Result<core::SyncResult, String> {
+    let options = options.unwrap_or_default();
+    log::info!("Syncing {:?} to {:?} ({:?})", source, target, options);
+    let source_session = state.session(Some(source.profile.clone()))?;
+    let target_session = state.session(Some(target.profile.clone()))?;
+    let source_prefix = source.key_encoding.decode(&source.prefix)?;
+    let target_prefix = target.key_encoding.decode(&target.prefix)?;
+    let keys = options
+        .keys
+        .map(|keys| {
+            keys.iter()
+                .map(|key| source.key_encoding.decode(key))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+
+    core::sync_prefix(
+        (&source_session, &source_prefix),
+        (&target_session, &target_prefix),
+        &core::SyncOptions {
+            keys,
+            dry_run: options.dry_run,
+            delete_extraneous: options.delete_extraneous,
+            preserve_lease: options.preserve_lease,
+        },
+    )
+    .await
+    .inspect(|res| {
+        let verb = if res.dry_run { "Would copy" } else { "Copied" };
+        log::info!(
+            "{} {} keys, {} deleted, {} unchanged",
+            verb,
+            res.copied.len(),
+            res.deleted.len(),
+            res.unchanged
+        )
+    })
+    .inspect_err(|e| log::error!("Failed to sync prefix: {}", e))
+}
+
+#[tauri::command]
+async fn get_cluster_info(
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<ClusterInfo, String> {
     log::debug!("Getting cluster info");
-    let mut state = state.lock().await;
+    let session = state.session(profile)?;
 
     // Get cluster members
-    let members = core::get_cluster_members(&mut state)
+    let members = core::get_cluster_members(&session)
         .await
         .inspect_err(|e| log::error!("Failed to get cluster members: {}", e))?;
 
     // Get cluster status
-    let status = core::get_cluster_status(&mut state)
+    let status = core::get_cluster_status(&session)
         .await
         .inspect_err(|e| log::error!("Failed to get cluster status: {}", e))?;
 
     // Convert members to serializable format
-    let members_info: Vec<MemberInfo> = members
-        .iter()
-        .map(|m| MemberInfo {
-            id: m.id(),
-            name: m.name().to_string(),
-            peer_urls: m.peer_urls().to_vec(),
-            client_urls: m.client_urls().to_vec(),
-        })
-        .collect();
+    let members_info: Vec<MemberInfo> = members.iter().map(MemberInfo::from).collect();
+
+    // Ask every member directly, the status above comes from whichever one the client picked
+    let member_statuses = core::get_member_statuses(&session.profile, &members).await;
+
+    // Only admin users may list alarms once auth is enabled, the rest is still useful
+    let (alarms, alarms_error) = match core::list_alarms(&session).await {
+        Ok(alarms) => (alarms, None),
+        Err(e) => {
+            log::warn!("Failed to list alarms: {}", e);
+            (Vec::new(), Some(e))
+        }
+    };
+
+    let header = status
+        .header()
+        .ok_or_else(|| "Status response has no header".to_string())
+        .inspect_err(|e| log::error!("Failed to get cluster status: {}", e))?;
 
     Ok(ClusterInfo {
-        cluster_id: status.header().unwrap().cluster_id(),
-        member_id: status.header().unwrap().member_id(),
+        cluster_id: header.cluster_id(),
+        member_id: header.member_id(),
         version: status.version().to_string(),
         db_size: status.db_size(),
         raft_index: status.raft_index(),
         raft_term: status.raft_term(),
         leader: status.leader(),
         members: members_info,
+        member_statuses,
+        alarms,
+        alarms_error,
     })
 }
 
 #[derive(Serialize)]
 struct MemberInfo {
     id: u64,
+    // Exact ID as printed by etcdctl, `id` loses precision in JavaScript
+    hex_id: String,
     name: String,
     peer_urls: Vec<String>,
     client_urls: Vec<String>,
+    is_learner: bool,
+}
+
+impl From<&etcd_client::Member> for MemberInfo {
+    fn from(m: &etcd_client::Member) -> Self {
+        MemberInfo {
+            id: m.id(),
+            hex_id: format!("{:x}", m.id()),
+            name: m.name().to_string(),
+            peer_urls: m.peer_urls().to_vec(),
+            client_urls: m.client_urls().to_vec(),
+            is_learner: m.is_learner(),
+        }
+    }
+}
+
+fn parse_member_id(hex_id: &str) -> Result<u64, String> {
+    u64::from_str_radix(hex_id.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("Invalid member ID {}: {}", hex_id, e))
+}
+
+async fn list_member_info(session: &Session) -> Result<Vec<MemberInfo>, String> {
+    core::get_cluster_members(session)
+        .await
+        .map(|members| members.iter().map(MemberInfo::from).collect())
+        .inspect_err(|e| log::error!("Failed to get cluster members: {}", e))
+}
+
+#[tauri::command]
+async fn add_member(
+    peer_urls: Vec<String>,
+    is_learner: Option<bool>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<MemberInfo>, String> {
+    let is_learner = is_learner.unwrap_or(false);
+    log::info!(
+        "Adding member with peer URLs {:?} (learner: {})",
+        peer_urls,
+        is_learner
+    );
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    let member = core::add_member(&peer_urls, is_learner, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to add member: {}", e))?;
+    log::info!("Added member {:x}", member.id());
+    list_member_info(&session).await
+}
+
+#[tauri::command]
+async fn remove_member(
+    member_id: String,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<MemberInfo>, String> {
+    log::info!("Removing member {}", member_id);
+    let id = parse_member_id(&member_id)?;
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::remove_member(id, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to remove member {}: {}", member_id, e))?;
+    list_member_info(&session).await
+}
+
+#[tauri::command]
+async fn update_member(
+    member_id: String,
+    peer_urls: Vec<String>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<MemberInfo>, String> {
+    log::info!(
+        "Updating peer URLs of member {} to {:?}",
+        member_id,
+        peer_urls
+    );
+    let id = parse_member_id(&member_id)?;
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::update_member(id, &peer_urls, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to update member {}: {}", member_id, e))?;
+    list_member_info(&session).await
+}
+
+#[tauri::command]
+async fn promote_member(
+    member_id: String,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<MemberInfo>, String> {
+    log::info!("Promoting learner {}", member_id);
+    let id = parse_member_id(&member_id)?;
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::promote_member(id, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to promote member {}: {}", member_id, e))?;
+    list_member_info(&session).await
+}
+
+#[tauri::command]
+async fn defragment_member(
+    member_id: String,
+    dry_run: Option<bool>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::MaintenanceResult, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    log::info!("Defragmenting member {} (dry run: {})", member_id, dry_run);
+    let id = parse_member_id(&member_id)?;
+    let session = state.session(profile)?;
+    if !dry_run {
+        session.ensure_unlocked()?;
+    }
+    core::defragment_member(id, dry_run, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to defragment member {}: {}", member_id, e))
+}
+
+#[tauri::command]
+async fn compact(
+    revision: i64,
+    physical: Option<bool>,
+    dry_run: Option<bool>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::MaintenanceResult, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    log::info!("Compacting to revision {} (dry run: {})", revision, dry_run);
+    let session = state.session(profile)?;
+    if !dry_run {
+        session.ensure_unlocked()?;
+    }
+    core::compact(revision, physical.unwrap_or(false), dry_run, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to compact to revision {}: {}", revision, e))
+}
+
+#[tauri::command]
+async fn move_leader(
+    member_id: String,
+    dry_run: Option<bool>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::MaintenanceResult, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    log::info!(
+        "Moving leader to member {} (dry run: {})",
+        member_id,
+        dry_run
+    );
+    let id = parse_member_id(&member_id)?;
+    let session = state.session(profile)?;
+    if !dry_run {
+        session.ensure_unlocked()?;
+    }
+    core::move_leader(id, dry_run, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to move leader to {}: {}", member_id, e))
+}
+
+/// Hash the key-value store of every member to detect divergence
+#[tauri::command]
+async fn hash_kv(
+    revision: Option<i64>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::HashKvResult, String> {
+    log::info!("Hashing members at revision {:?}", revision);
+    let session = state.session(profile)?;
+    core::hash_kv_all(revision.unwrap_or(0), &session)
+        .await
+        .inspect(|res| {
+            if !res.complete {
+                log::warn!(
+                    "Some members are unreachable or compacted differently: {:?}",
+                    res.members
+                )
+            } else if !res.consistent {
+                log::warn!("Members have diverged: {:?}", res.members)
+            }
+        })
+        .inspect_err(|e| log::error!("Failed to hash members: {}", e))
+}
+
+fn emit_snapshot_progress_event(app_handle: &tauri::AppHandle, payload: &core::SnapshotProgress) {
+    if let Err(err) = app_handle.emit(SNAPSHOT_PROGRESS_EVENT, payload) {
+        log::error!("Failed to emit snapshot-progress event: {err}");
+    }
+}
+
+/// Save a snapshot of a member's database to ```path```.
+///
+/// Progress is reported with ```snapshot-progress``` events.
+#[tauri::command]
+async fn snapshot_member(
+    member_id: String,
+    path: PathBuf,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<core::SnapshotResult, String> {
+    log::info!(
+        "Saving snapshot of member {} to {}",
+        member_id,
+        path.display()
+    );
+    let id = parse_member_id(&member_id)?;
+    let session = state.session(profile)?;
+    core::snapshot_member(
+        id,
+        &path,
+        |progress| emit_snapshot_progress_event(&app_handle, progress),
+        &session,
+    )
+    .await
+    .inspect(|res| {
+        log::info!(
+            "Saved {} bytes snapshot at revision {} (sha256 {})",
+            res.size,
+            res.revision,
+            res.sha256
+        )
+    })
+    .inspect_err(|e| log::error!("Failed to save snapshot of member {}: {}", member_id, e))
+}
+
+#[tauri::command]
+async fn list_alarms(
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<core::AlarmInfo>, String> {
+    log::debug!("Listing alarms");
+    let session = state.session(profile)?;
+    core::list_alarms(&session)
+        .await
+        .inspect_err(|e| log::error!("Failed to list alarms: {}", e))
+}
+
+/// Disarm an alarm of a member, returns the alarms still active
+#[tauri::command]
+async fn disarm_alarm(
+    member_id: String,
+    alarm: core::AlarmKind,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<core::AlarmInfo>, String> {
+    log::info!("Disarming {:?} alarm of member {}", alarm, member_id);
+    let id = parse_member_id(&member_id)?;
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::disarm_alarm(id, alarm, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to disarm alarm of member {}: {}", member_id, e))
+}
+
+#[tauri::command]
+async fn list_users(
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<core::UserInfo>, String> {
+    log::debug!("Listing users");
+    let session = state.session(profile)?;
+    core::list_users(&session)
+        .await
+        .inspect_err(|e| log::error!("Failed to list users: {}", e))
+}
+
+#[tauri::command]
+async fn add_user(
+    name: String,
+    password: Option<String>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Adding user {}", name);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::add_user(&name, password.as_deref(), &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to add user {}: {}", name, e))
+}
+
+#[tauri::command]
+async fn delete_user(
+    name: String,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Deleting user {}", name);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::delete_user(&name, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to delete user {}: {}", name, e))
+}
+
+#[tauri::command]
+async fn change_password(
+    name: String,
+    password: String,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Changing password of user {}", name);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::change_password(&name, &password, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to change password of user {}: {}", name, e))
+}
+
+#[tauri::command]
+async fn grant_role(
+    user: String,
+    role: String,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Granting role {} to user {}", role, user);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::grant_role(&user, &role, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to grant role {} to {}: {}", role, user, e))
+}
+
+#[tauri::command]
+async fn revoke_role(
+    user: String,
+    role: String,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Revoking role {} from user {}", role, user);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::revoke_role(&user, &role, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to revoke role {} from {}: {}", role, user, e))
+}
+
+#[tauri::command]
+async fn list_roles(
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<core::RoleInfo>, String> {
+    log::debug!("Listing roles");
+    let session = state.session(profile)?;
+    core::list_roles(&session)
+        .await
+        .inspect_err(|e| log::error!("Failed to list roles: {}", e))
+}
+
+#[tauri::command]
+async fn add_role(
+    name: String,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Adding role {}", name);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::add_role(&name, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to add role {}: {}", name, e))
+}
+
+#[tauri::command]
+async fn delete_role(
+    name: String,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Deleting role {}", name);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::delete_role(&name, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to delete role {}: {}", name, e))
+}
+
+#[tauri::command]
+async fn grant_permission(
+    role: String,
+    permission: core::PermissionRequest,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Granting {:?} to role {}", permission, role);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::grant_permission(&role, &permission, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to grant permission to role {}: {}", role, e))
+}
+
+#[tauri::command]
+async fn revoke_permission(
+    role: String,
+    permission: core::PermissionRequest,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Revoking {:?} from role {}", permission, role);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::revoke_permission(&role, &permission, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to revoke permission from role {}: {}", role, e))
+}
+
+/// Tell whether ```user``` may read and write ```key```, and which roles allow it
+#[tauri::command]
+async fn check_permission(
+    user: String,
+    key: String,
+    key_encoding: Option<client::Encoding>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::EffectivePermission, String> {
+    log::debug!("Checking permissions of user {} on {}", user, key);
+    let key_bytes = key_encoding.unwrap_or_default().decode(&key)?;
+    let session = state.session(profile)?;
+    core::check_permission(&user, &key_bytes, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to check permissions of user {}: {}", user, e))
+}
+
+#[tauri::command]
+async fn enable_auth(profile: Option<String>, state: State<'_, AppState>) -> Result<(), String> {
+    log::info!("Enabling authentication");
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::enable_auth(&session)
+        .await
+        .inspect_err(|e| log::error!("Failed to enable authentication: {}", e))?;
+    // Reconnect so that the client authenticates from now on
+    session.reset_client();
+    Ok(())
+}
+
+#[tauri::command]
+async fn disable_auth(profile: Option<String>, state: State<'_, AppState>) -> Result<(), String> {
+    log::info!("Disabling authentication");
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::disable_auth(&session)
+        .await
+        .inspect_err(|e| log::error!("Failed to disable authentication: {}", e))
+}
+
+/// List the holder and the waiters of a lock or an election
+#[tauri::command]
+async fn list_contenders(
+    name: String,
+    key_encoding: Option<client::Encoding>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::Contenders, String> {
+    log::debug!("Listing contenders of {}", name);
+    let name_bytes = key_encoding.unwrap_or_default().decode(&name)?;
+    let session = state.session(profile)?;
+    core::list_contenders(&name_bytes, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to list contenders of {}: {}", name, e))
+}
+
+/// Start following the leader of an election.
+///
+/// Leaders are emitted as ```election-leader``` events tagged with the returned ID.
+#[tauri::command]
+async fn start_election_observe(
+    name: String,
+    key_encoding: Option<client::Encoding>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<u64, String> {
+    log::info!("Observing election {}", name);
+    let name_bytes = key_encoding.unwrap_or_default().decode(&name)?;
+    let session = state.session(profile)?;
+    let stream = core::observe_election(&name_bytes, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to observe election {}: {}", name, e))?;
+    Ok(state
+        .election_observers()
+        .spawn(app_handle, session.profile.name, stream))
+}
+
+#[tauri::command]
+async fn stop_election_observe(observe_id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    log::info!("Stopping election observer {}", observe_id);
+    state
+        .election_observers()
+        .stop(observe_id)
+        .inspect_err(|e| log::error!("Failed to stop election observer: {}", e))
+}
+
+/// Revoke the lease of the holder of a lock or an election, which deletes every key
+/// attached to that lease.
+///
+/// Fails if ```expected_holder``` (in ```key_encoding```) no longer holds the lock.
+#[tauri::command]
+async fn force_release_lock(
+    name: String,
+    key_encoding: Option<client::Encoding>,
+    expected_holder: Option<String>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::ForceReleaseResult, String> {
+    log::info!("Force-releasing {}", name);
+    let key_encoding = key_encoding.unwrap_or_default();
+    let name_bytes = key_encoding.decode(&name)?;
+    let expected_holder = expected_holder
+        .map(|key| key_encoding.decode(&key))
+        .transpose()?;
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::force_release(&name_bytes, expected_holder.as_deref(), &session)
+        .await
+        .inspect(|res| {
+            log::info!(
+                "Released {} held by {}, deleting {} keys",
+                name,
+                res.holder.item.key,
+                res.deleted_keys.len()
+            )
+        })
+        .inspect_err(|e| log::error!("Failed to force-release {}: {}", name, e))
 }
 
 #[derive(Serialize)]
@@ -326,13 +1298,17 @@ struct ClusterInfo {
     raft_term: u64,
     leader: u64,
     members: Vec<MemberInfo>,
+    member_statuses: Vec<core::MemberStatus>,
+    alarms: Vec<core::AlarmInfo>,
+    // Why `alarms` is empty when they could not be listed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alarms_error: Option<String>,
 }
 
 #[tauri::command]
-async fn get_config(state: State<'_, Mutex<AppState>>) -> Result<config::AppConfig, String> {
-    let app_state = state.lock().await;
+async fn get_config(state: State<'_, AppState>) -> Result<config::AppConfig, String> {
     // Return a clone of the config
-    Ok(app_state.app_config.clone())
+    Ok(config::AppConfig::clone(&state.config()))
 }
 
 #[tauri::command]
@@ -343,55 +1319,116 @@ async fn get_default_config() -> Result<config::AppConfig, String> {
 #[tauri::command]
 async fn update_config(
     config: config::AppConfig,
-    state: State<'_, Mutex<AppState>>,
+    state: State<'_, AppState>,
     update_worker_control: State<'_, UpdateCheckWorkerControl>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
-    let mut app_state = state.lock().await;
-
-    // Save config to disk
+    // Save config to disk, with the passwords moved to the secret store, and update
+    // the in-memory config with the new settings. Clients of other profiles stay
+    // pooled, only the ones of removed or reconfigured profiles are dropped
     let path = config::AppConfig::get_config_path(&app_handle)?;
-    log::debug!("Updating config: {:?}", config);
-    let file = match File::create(&path) {
-        Ok(f) => f,
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            // Create parent directory only when the path doesn't exist, then retry
-            let parent = path.parent().ok_or(format!(
-                "Failed to determine parent directory for config path: {:?}",
-                &path
-            ))?;
-
-            log::info!("Config directory not found at {:?}, creating", parent);
-
-            std::fs::create_dir_all(parent)
-                .map_err(|err| format!("Failed to create config directory: {}", err))?;
-            File::create(&path).map_err(|err| {
-                format!("Failed to create config file after creating directory: {err}")
-            })?
-        }
-        Err(e) => {
-            log::error!("Failed to create config file at {:?}: {}", &path, e);
-            return Err(format!("Failed to create config file: {}", e));
-        }
-    };
+    state.save_config(config, &path)?;
 
-    serde_json::to_writer_pretty(file, &config)
-        .map_err(|e| format!("Failed to write config: {}", e))?;
+    log::info!("Configuration updated successfully");
+    update_worker_control.wake_signal.notify_waiters();
+    Ok(())
+}
 
-    // Check if current profile changed
-    let should_reconnect = app_state.app_config.current_profile != config.current_profile;
+/// Write the profiles named ```names``` to a file that ```import_profiles``` reads.
+///
+/// Passwords are only included with ```include_secrets```, returns the number of profiles.
+#[tauri::command]
+async fn export_profiles(
+    names: Vec<String>,
+    path: String,
+    include_secrets: bool,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    log::info!("Exporting profiles {:?} to {}", names, path);
+    let bundle = state
+        .config()
+        .export_profiles(&names, include_secrets)
+        .inspect_err(|e| log::error!("Failed to export profiles: {}", e))?;
+    let content = serde_json::to_vec_pretty(&bundle).map_err(|e| e.to_string())?;
+    secrets::write_private(std::path::Path::new(&path), &content)
+        .map_err(|e| format!("Failed to write {}: {}", path, e))
+        .inspect_err(|e| log::error!("Failed to export profiles: {}", e))?;
+    Ok(bundle.profiles.len())
+}
 
-    // Update in-memory config
-    app_state.app_config = config; // Update in-memory config with the new settings
+#[tauri::command]
+async fn import_profiles(
+    path: String,
+    collision: Option<config::NameCollision>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<config::ProfileImportResult, String> {
+    log::info!("Importing profiles from {}", path);
+    let content = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let bundle = config::parse_bundle(&content)
+        .inspect_err(|e| log::error!("Failed to import profiles: {}", e))?;
+
+    let config_path = config::AppConfig::get_config_path(&app_handle)?;
+    let result = state
+        .update_config(&config_path, |config| {
+            Ok(config.import_profiles(bundle, collision.unwrap_or_default()))
+        })
+        .inspect_err(|e| log::error!("Failed to save imported profiles: {}", e))?;
+    log::info!("Imported profiles {:?}", result.imported);
+    Ok(result)
+}
 
-    // Re-initialize client if profile changed
-    if should_reconnect {
-        log::info!("Current profile changed, resetting client");
-        app_state.etcd_client = None; // Reset the client
-    }
+/// Build a draft profile from etcdctl's ```ETCDCTL_*``` variables.
+///
+/// ```env_text``` holds ```KEY=value``` lines, the app's own environment is used without it.
+#[tauri::command]
+async fn profile_from_etcdctl_env(
+    env_text: Option<String>,
+) -> Result<config::ProfileDraft, String> {
+    log::info!("Building a profile from etcdctl environment");
+    let env = match env_text {
+        Some(text) => config::parse_env_text(&text),
+        None => std::env::vars().collect(),
+    };
+    config::profile_from_etcdctl_env(&env)
+        .inspect_err(|e| log::error!("Failed to build profile from environment: {}", e))
+}
 
-    log::info!("Configuration updated successfully");
-    update_worker_control.wake_signal.notify_waiters();
+#[derive(Serialize)]
+struct SecretStoreStatus {
+    backend: secrets::SecretBackend,
+    unlocked: bool,
+}
+
+#[tauri::command]
+async fn get_secret_store_status(state: State<'_, AppState>) -> Result<SecretStoreStatus, String> {
+    let secret_store = state.secret_store();
+    Ok(SecretStoreStatus {
+        backend: secret_store.backend(),
+        unlocked: secret_store.is_unlocked(),
+    })
+}
+
+/// Unlock the encrypted-file secret store and load the profile passwords from it.
+///
+/// The first unlock sets the master passphrase.
+#[tauri::command]
+async fn unlock_secret_store(
+    passphrase: String,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    log::info!("Unlocking secret store");
+    state
+        .secret_store()
+        .unlock(&passphrase)
+        .inspect_err(|e| log::error!("Failed to unlock secret store: {}", e))?;
+    let path = config::AppConfig::get_config_path(&app_handle)?;
+    state
+        .load_secrets(&path)
+        .inspect_err(|e| log::error!("Failed to load secrets: {}", e))?;
+    // Clients may have been created without their password
+    state.clients.clear();
     Ok(())
 }
 
@@ -632,30 +1669,183 @@ async fn get_system_fonts() -> Result<Vec<String>, String> {
 async fn get_key_at_revision(
     key: String,
     revision: i64,
-    state: State<'_, Mutex<AppState>>,
+    key_encoding: Option<client::Encoding>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
 ) -> Result<Option<client::Item>, String> {
     log::debug!("Getting key {} at revision {}", key, revision);
-    let mut state = state.lock().await;
-    core::get_key_at_revision(&key, revision, &mut state)
+    let key_bytes = key_encoding.unwrap_or_default().decode(&key)?;
+    let session = state.session(profile)?;
+    core::get_key_at_revision(&key_bytes, revision, &session)
         .await
         .inspect_err(|e| log::error!("Failed to get key at revision: {}", e))
 }
 
+/// Start watching a key or prefix.
+///
+/// Events are emitted as ```etcd-watch``` events tagged with the returned watch ID.
+#[tauri::command]
+async fn start_watch(
+    request: watch::WatchRequest,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<u64, String> {
+    log::info!("Starting watch: {:?}", request);
+    let key = request.key_encoding.decode(&request.key)?;
+    let session = state.session(profile)?;
+    let (watcher, stream) = core::watch(&key, &request, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to start watch on {}: {}", request.key, e))?;
+    Ok(state
+        .watches()
+        .spawn(app_handle, session.profile.name, watcher, stream))
+}
+
+#[tauri::command]
+async fn stop_watch(watch_id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    log::info!("Stopping watch {}", watch_id);
+    let watcher = state
+        .watches()
+        .stop(watch_id)
+        .inspect_err(|e| log::error!("Failed to stop watch: {}", e))?;
+    watch::cancel(watch_id, watcher).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_leases(
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    log::debug!("Listing leases");
+    let session = state.session(profile)?;
+    core::list_leases(&session)
+        .await
+        .inspect_err(|e| log::error!("Failed to list leases: {}", e))
+}
+
+#[tauri::command]
+async fn get_lease_info(
+    lease_id: i64,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<lease::LeaseInfo, String> {
+    log::debug!("Getting lease info: {}", lease_id);
+    let session = state.session(profile)?;
+    core::get_lease_info(lease_id, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to get lease {}: {}", lease_id, e))
+}
+
+#[tauri::command]
+async fn grant_lease(
+    ttl: i64,
+    lease_id: Option<i64>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    log::info!("Granting lease with TTL {}s", ttl);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::grant_lease(ttl, lease_id, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to grant lease: {}", e))
+}
+
+#[tauri::command]
+async fn revoke_lease(
+    lease_id: i64,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Revoking lease: {}", lease_id);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    let _ = state
+        .lease_keep_alives()
+        .stop(&session.profile.name, lease_id);
+    core::revoke_lease(lease_id, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to revoke lease {}: {}", lease_id, e))
+}
+
+/// Refresh a lease once and return its new TTL
+#[tauri::command]
+async fn keep_alive_lease(
+    lease_id: i64,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    log::info!("Keeping lease alive: {}", lease_id);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    core::keep_alive_lease_once(lease_id, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to keep lease {} alive: {}", lease_id, e))
+}
+
+/// Keep a lease alive in the background until stopped.
+///
+/// Every refresh is reported as a ```lease-keep-alive``` event.
+#[tauri::command]
+async fn start_lease_keep_alive(
+    lease_id: i64,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    log::info!("Starting keep-alive for lease: {}", lease_id);
+    let session = state.session(profile)?;
+    session.ensure_unlocked()?;
+    let (keeper, stream) = core::lease_keep_alive(lease_id, &session)
+        .await
+        .inspect_err(|e| log::error!("Failed to start keep-alive for {}: {}", lease_id, e))?;
+    state
+        .lease_keep_alives()
+        .spawn(app_handle, session.profile.name, keeper, stream);
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_lease_keep_alive(
+    lease_id: i64,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    log::info!("Stopping keep-alive for lease: {}", lease_id);
+    let session = state.session(profile)?;
+    state
+        .lease_keep_alives()
+        .stop(&session.profile.name, lease_id)
+}
+
+#[tauri::command]
+async fn get_key_history(
+    key: String,
+    key_encoding: Option<client::Encoding>,
+    from_revision: Option<i64>,
+    limit: Option<usize>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<core::KeyHistory, String> {
+    log::debug!("Getting history of key {}", key);
+    let key_bytes = key_encoding.unwrap_or_default().decode(&key)?;
+    let session = state.session(profile)?;
+    core::get_key_history(&key_bytes, from_revision, limit, &session)
+        .await
+        .inspect(|h| log::debug!("Found {} versions of key {}", h.versions.len(), key))
+        .inspect_err(|e| log::error!("Failed to get key history: {}", e))
+}
+
 #[tauri::command]
 async fn fetch_metrics(
     endpoint: config::Endpoint,
-    state: State<'_, Mutex<AppState>>,
+    profile: Option<String>,
+    state: State<'_, AppState>,
 ) -> Result<Vec<metrics::ParsedMetricFamily>, String> {
-    let profile = {
-        let state = state.lock().await;
-        state
-            .app_config
-            .get_current_profile()
-            .cloned()
-            .ok_or_else(|| "No current profile set".to_string())?
-    };
-
-    parse_metrics_text(fetch_metrics_text(&profile, &endpoint).await?)
+    let session = state.session(profile)?;
+    parse_metrics_text(fetch_metrics_text(&session.profile, &endpoint).await?)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -699,8 +1889,41 @@ pub fn run() {
             list_keys_only,
             get_values_in_range,
             put_key,
+            execute_txn,
             delete_key,
+            delete_prefix,
+            delete_range,
+            export_prefix,
+            export_range,
+            import_keys,
+            diff_prefixes,
+            sync_prefix,
             get_cluster_info,
+            add_member,
+            remove_member,
+            update_member,
+            promote_member,
+            defragment_member,
+            compact,
+            move_leader,
+            hash_kv,
+            snapshot_member,
+            list_alarms,
+            disarm_alarm,
+            list_users,
+            add_user,
+            delete_user,
+            change_password,
+            grant_role,
+            revoke_role,
+            list_roles,
+            add_role,
+            delete_role,
+            grant_permission,
+            revoke_permission,
+            check_permission,
+            enable_auth,
+            disable_auth,
             get_config,
             get_default_config,
             update_config,
@@ -716,11 +1939,30 @@ pub fn run() {
             delete_path_history,
             get_system_fonts,
             get_key_at_revision,
+            get_key_history,
             format_timestamp,
             fetch_metrics,
+            start_watch,
+            stop_watch,
+            list_leases,
+            get_lease_info,
+            grant_lease,
+            revoke_lease,
+            keep_alive_lease,
+            start_lease_keep_alive,
+            stop_lease_keep_alive,
+            list_contenders,
+            start_election_observe,
+            stop_election_observe,
+            force_release_lock,
+            get_secret_store_status,
+            unlock_secret_store,
+            export_profiles,
+            import_profiles,
+            profile_from_etcdctl_env,
         ])
         .setup(|app| {
-            app.manage(tokio::sync::Mutex::new(AppState::new(app.handle())?));
+            app.manage(AppState::new(app.handle())?);
 
             let update_worker_control = UpdateCheckWorkerControl::default();
             let worker_control = update_worker_control.clone();
@@ -731,6 +1973,7 @@ pub fn run() {
             tauri::async_runtime::spawn(async move {
                 update_check_worker(app_handle, worker_control).await;
             });
+            tauri::async_runtime::spawn(pool::pool_maintenance_worker(app.handle().clone()));
 
             Ok(())
         })
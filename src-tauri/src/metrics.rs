@@ -45,11 +45,19 @@ pub struct ParsedMetricSample {
 
 pub async fn fetch_metrics_text(profile: &Profile, endpoint: &Endpoint) -> Result<String, String> {
     let metrics_path = match profile.metrics_path.as_deref() {
-        None => "/metrics".to_string(),
+        None | Some("") => return Err("Metrics are disabled for this profile".to_string()),
         Some(path) if !path.starts_with('/') => format!("/{path}"),
         Some(path) => path.to_string(),
     };
 
+    let mut url = reqwest::Url::parse(endpoint.to_string().as_str())
+        .map_err(|e| format!("Invalid endpoint URL: {e}"))?;
+    if url.scheme() == "" {
+        url.set_scheme("http")
+            .map_err(|_| format!("Failed to set URL scheme: for endpoint: {endpoint}"))?;
+    }
+    url.set_path(&metrics_path);
+
     let mut builder = reqwest::Client::builder();
     builder = builder.timeout(Duration::from_millis(profile.timeout_ms.unwrap_or(5000)));
 
@@ -57,18 +65,42 @@ pub async fn fetch_metrics_text(profile: &Profile, endpoint: &Endpoint) -> Resul
         builder = builder.connect_timeout(Duration::from_millis(connect_timeout));
     }
 
+    if let Some(tls) = &profile.tls {
+        let material = tls.load()?;
+        builder = builder.use_rustls_tls();
+        if let Some(ca_cert) = material.ca_cert {
+            let cert = reqwest::Certificate::from_pem(&ca_cert)
+                .map_err(|e| format!("Invalid CA certificate: {e}"))?;
+            builder = builder.add_root_certificate(cert);
+        }
+        if let Some((mut cert, key)) = material.identity {
+            // reqwest expects the certificate chain and the private key in one PEM buffer
+            cert.push(b'\n');
+            cert.extend_from_slice(&key);
+            let identity = reqwest::Identity::from_pem(&cert)
+                .map_err(|e| format!("Invalid client certificate or key: {e}"))?;
+            builder = builder.identity(identity);
+        }
+        if let Some(server_name) = material.server_name {
+            // Request the overridden name so the certificate is verified against it,
+            // but keep connecting to the configured address
+            let host = url.host_str().unwrap_or_default().to_string();
+            let port = url.port_or_known_default().unwrap_or(endpoint.port);
+            let addr = tokio::net::lookup_host((host.as_str(), port))
+                .await
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .ok_or_else(|| format!("Failed to resolve endpoint address: {endpoint}"))?;
+            url.set_host(Some(&server_name))
+                .map_err(|e| format!("Invalid TLS server name '{server_name}': {e}"))?;
+            builder = builder.resolve(&server_name, addr);
+        }
+    }
+
     let client = builder
         .build()
         .map_err(|e| format!("Failed to build HTTP client: {e}"))?;
 
-    let mut url = reqwest::Url::parse(endpoint.to_string().as_str())
-        .map_err(|e| format!("Invalid endpoint URL: {e}"))?;
-    if url.scheme() == "" {
-        url.set_scheme("http")
-            .map_err(|_| format!("Failed to set URL scheme: for endpoint: {endpoint}"))?;
-    }
-    url.set_path(&metrics_path);
-
     log::debug!("Fetching metrics from {url}");
 
     let mut request = client.get(url.clone());
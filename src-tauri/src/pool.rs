@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use etcd_client::Client;
+use tauri::Manager;
+
+use crate::client::new_connect;
+use crate::config::Profile;
+use crate::state::AppState;
+
+// Clients unused for this long are dropped, they reconnect on next use
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct PooledClient {
+    client: Client,
+    // Tells a client apart from the one that replaced it under the same profile
+    generation: u64,
+    // Settings the client was connected with, the profile may have changed since
+    profile: Profile,
+    last_used: Instant,
+}
+
+// Locked while connecting, so that concurrent operations on a profile share one connection
+type Slot = Arc<tokio::sync::Mutex<Option<PooledClient>>>;
+
+/// Live clients keyed by profile name, so that switching profiles does not reconnect.
+///
+/// Clients are cloned out of the pool, operations never hold a lock while they run.
+#[derive(Default)]
+pub struct ClientPool {
+    next_generation: AtomicU64,
+    slots: std::sync::Mutex<HashMap<String, Slot>>,
+}
+
+impl ClientPool {
+    fn slots(&self) -> std::sync::MutexGuard<'_, HashMap<String, Slot>> {
+        self.slots
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn slot(&self, name: &str) -> Slot {
+        self.slots().entry(name.to_string()).or_default().clone()
+    }
+
+    /// The client of `profile` and its generation, connecting first if there is none
+    pub async fn get(&self, profile: &Profile) -> Result<(Client, u64), String> {
+        self.checkout(profile, None).await
+    }
+
+    /// Replace the client of generation `stale`, which failed.
+    ///
+    /// When concurrent operations fail together, only the first one reconnects and the
+    /// others get its client.
+    pub async fn refresh(&self, profile: &Profile, stale: u64) -> Result<(Client, u64), String> {
+        self.checkout(profile, Some(stale)).await
+    }
+
+    async fn checkout(
+        &self,
+        profile: &Profile,
+        stale: Option<u64>,
+    ) -> Result<(Client, u64), String> {
+        let slot = self.slot(&profile.name);
+        let mut slot = slot.lock().await;
+        let reusable = slot.as_ref().is_some_and(|pooled| {
+            Some(pooled.generation) != stale && pooled.profile.same_connection(profile)
+        });
+        if !reusable {
+            let client = new_connect(profile).await?;
+            *slot = Some(PooledClient {
+                client,
+                generation: self.next_generation.fetch_add(1, Ordering::Relaxed) + 1,
+                profile: profile.clone(),
+                last_used: Instant::now(),
+            });
+        }
+        let pooled = slot.as_mut().expect("Client should be pooled");
+        pooled.last_used = Instant::now();
+        Ok((pooled.client.clone(), pooled.generation))
+    }
+
+    /// Drop the client of a profile, e.g. because its settings or credentials changed
+    pub fn evict(&self, name: &str) {
+        if self.slots().remove(name).is_some() {
+            log::debug!("Dropped client of profile {name}");
+        }
+    }
+
+    pub fn clear(&self) {
+        self.slots().clear();
+    }
+
+    /// Drop idle clients, and return the other ones to health-check
+    fn sweep(&self) -> Vec<(String, u64, Client)> {
+        let mut clients = Vec::new();
+        self.slots().retain(|name, slot| {
+            // A slot in use, e.g. connecting, is not idle
+            let Ok(slot) = slot.try_lock() else {
+                return true;
+            };
+            let Some(pooled) = slot.as_ref() else {
+                return false;
+            };
+            if pooled.last_used.elapsed() >= IDLE_TIMEOUT {
+                log::debug!("Dropping idle client of profile {name}");
+                return false;
+            }
+            clients.push((name.clone(), pooled.generation, pooled.client.clone()));
+            true
+        });
+        clients
+    }
+
+    /// Drop the client of `name` if it is still the one health-checked as `generation`
+    async fn evict_generation(&self, name: &str, generation: u64) {
+        let Some(slot) = self.slots().get(name).cloned() else {
+            return;
+        };
+        let mut slot = slot.lock().await;
+        if slot
+            .as_ref()
+            .is_some_and(|pooled| pooled.generation == generation)
+        {
+            *slot = None;
+        }
+    }
+}
+
+/// Periodically drop idle clients and the ones whose cluster stopped answering
+pub async fn pool_maintenance_worker(app_handle: tauri::AppHandle) {
+    let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let state = app_handle.state::<AppState>();
+
+        for (name, generation, mut client) in state.clients.sweep() {
+            let error = match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, client.status()).await {
+                Ok(Ok(_)) => continue,
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => "timed out".to_string(),
+            };
+            log::warn!("Health check of profile {name} failed ({error}), dropping its client");
+            state.clients.evict_generation(&name, generation).await;
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+
+// Service name under which passwords are stored in the OS keyring
+const KEYRING_SERVICE: &str = "etcd-gui";
+const SECRETS_FILE_NAME: &str = "secrets.enc";
+const SECRETS_FILE_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    Keyring,
+    EncryptedFile,
+}
+
+/// Where profile passwords live, so that `config.json` only holds references to them
+pub trait SecretStore: Send {
+    fn backend(&self) -> SecretBackend;
+
+    /// Whether secrets can be read and written, without asking for a passphrase first
+    fn is_unlocked(&self) -> bool {
+        true
+    }
+
+    /// Unlock the store with its master passphrase, for backends that have one
+    fn unlock(&mut self, _passphrase: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, String>;
+
+    fn set(&mut self, id: &str, secret: &str) -> Result<(), String>;
+
+    /// Delete a secret, doing nothing if it does not exist
+    fn delete(&mut self, id: &str) -> Result<(), String>;
+}
+
+/// Open the OS keyring, or the encrypted file in `config_dir` when there is no
+/// usable keyring (e.g. headless Linux without a Secret Service daemon)
+pub fn open_store(config_dir: &Path) -> Box<dyn SecretStore> {
+    match KeyringStore::probe() {
+        Ok(()) => {
+            log::info!("Storing secrets in the OS keyring");
+            Box::new(KeyringStore)
+        }
+        Err(e) => {
+            log::warn!("OS keyring unavailable ({e}), falling back to an encrypted file");
+            Box::new(EncryptedFileStore::new(config_dir.join(SECRETS_FILE_NAME)))
+        }
+    }
+}
+
+pub struct KeyringStore;
+
+impl KeyringStore {
+    fn entry(id: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(KEYRING_SERVICE, id).map_err(|e| e.to_string())
+    }
+
+    /// Check that the keyring answers, a missing entry is fine
+    fn probe() -> Result<(), String> {
+        match Self::entry("probe")?.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn backend(&self) -> SecretBackend {
+        SecretBackend::Keyring
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, String> {
+        match Self::entry(id)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Failed to read secret {id} from the keyring: {e}")),
+        }
+    }
+
+    fn set(&mut self, id: &str, secret: &str) -> Result<(), String> {
+        Self::entry(id)?
+            .set_password(secret)
+            .map_err(|e| format!("Failed to write secret {id} to the keyring: {e}"))
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), String> {
+        match Self::entry(id)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!(
+                "Failed to delete secret {id} from the keyring: {e}"
+            )),
+        }
+    }
+}
+
+/// On-disk layout of [`EncryptedFileStore`], the secrets map encrypted as a whole
+#[derive(Serialize, Deserialize)]
+struct SecretsFile {
+    version: u32,
+    // Base64, the salt of the Argon2 key derivation
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+struct UnlockedSecrets {
+    key: Key<Aes256Gcm>,
+    salt: [u8; SALT_LEN],
+    secrets: HashMap<String, String>,
+}
+
+/// Secrets encrypted with AES-256-GCM, under a key derived from a master passphrase.
+///
+/// The store is locked until [`SecretStore::unlock`] is called. Unlocking a store
+/// whose file does not exist yet sets its passphrase.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    unlocked: Option<UnlockedSecrets>,
+}
+
+impl EncryptedFileStore {
+    pub fn new(path: PathBuf) -> Self {
+        EncryptedFileStore {
+            path,
+            unlocked: None,
+        }
+    }
+
+    fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>, String> {
+        let mut key = Key::<Aes256Gcm>::default();
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| format!("Failed to derive the secrets key: {e}"))?;
+        Ok(key)
+    }
+
+    fn read(path: &Path, passphrase: &str) -> Result<UnlockedSecrets, String> {
+        let content =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let file: SecretsFile = serde_json::from_slice(&content)
+            .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+        if file.version != SECRETS_FILE_VERSION {
+            return Err(format!("Unsupported secrets file version {}", file.version));
+        }
+        let decode = |field: &str| {
+            BASE64
+                .decode(field)
+                .map_err(|e| format!("Corrupted secrets file: {e}"))
+        };
+        let salt: [u8; SALT_LEN] = decode(&file.salt)?
+            .try_into()
+            .map_err(|_| "Corrupted secrets file: invalid salt".to_string())?;
+        let nonce = decode(&file.nonce)?;
+        if nonce.len() != 12 {
+            return Err("Corrupted secrets file: invalid nonce".to_string());
+        }
+
+        let key = Self::derive_key(passphrase, &salt)?;
+        // Authentication fails alike for a wrong passphrase and a tampered file
+        let plaintext = Aes256Gcm::new(&key)
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                decode(&file.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| "Wrong master passphrase".to_string())?;
+        let secrets = serde_json::from_slice(&plaintext)
+            .map_err(|e| format!("Corrupted secrets file: {e}"))?;
+
+        Ok(UnlockedSecrets { key, salt, secrets })
+    }
+
+    fn unlocked(&self) -> Result<&UnlockedSecrets, String> {
+        self.unlocked.as_ref().ok_or_else(|| {
+            "The secret store is locked, unlock it with the master passphrase".to_string()
+        })
+    }
+
+    fn unlocked_mut(&mut self) -> Result<&mut UnlockedSecrets, String> {
+        self.unlocked.as_mut().ok_or_else(|| {
+            "The secret store is locked, unlock it with the master passphrase".to_string()
+        })
+    }
+
+    /// Encrypt the secrets with a fresh nonce and replace the file atomically
+    fn save(&self) -> Result<(), String> {
+        let unlocked = self.unlocked()?;
+        let plaintext = serde_json::to_vec(&unlocked.secrets).map_err(|e| e.to_string())?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&unlocked.key)
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|e| format!("Failed to encrypt secrets: {e}"))?;
+        let file = SecretsFile {
+            version: SECRETS_FILE_VERSION,
+            salt: BASE64.encode(unlocked.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        let content = serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())?;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+        }
+        let mut tmp_path = self.path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        write_private(&tmp_path, &content)
+            .map_err(|e| format!("Failed to write {}: {e}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("Failed to write {}: {e}", self.path.display()))
+    }
+}
+
+/// Write a file only the current user can read
+pub fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn backend(&self) -> SecretBackend {
+        SecretBackend::EncryptedFile
+    }
+
+    fn is_unlocked(&self) -> bool {
+        self.unlocked.is_some()
+    }
+
+    fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        if passphrase.is_empty() {
+            return Err("The master passphrase cannot be empty".to_string());
+        }
+        if self.path.exists() {
+            self.unlocked = Some(Self::read(&self.path, passphrase)?);
+            return Ok(());
+        }
+
+        log::info!("Creating secrets file at {}", self.path.display());
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        self.unlocked = Some(UnlockedSecrets {
+            key: Self::derive_key(passphrase, &salt)?,
+            salt,
+            secrets: HashMap::new(),
+        });
+        // Write the empty store right away, which fixes the passphrase
+        self.save().inspect_err(|_| self.unlocked = None)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, String> {
+        Ok(self.unlocked()?.secrets.get(id).cloned())
+    }
+
+    fn set(&mut self, id: &str, secret: &str) -> Result<(), String> {
+        self.unlocked_mut()?
+            .secrets
+            .insert(id.to_string(), secret.to_string());
+        self.save()
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), String> {
+        if self.unlocked_mut()?.secrets.remove(id).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory under the system temp dir, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("etcd-gui-secrets-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn store(&self) -> EncryptedFileStore {
+            EncryptedFileStore::new(self.0.join(SECRETS_FILE_NAME))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn reopens_sealed_secrets() {
+        let dir = TempDir::new("reopen");
+        let mut store = dir.store();
+        assert!(!store.is_unlocked());
+        store.unlock("passphrase").unwrap();
+        store.set("profile-a", "secret a").unwrap();
+        store.set("profile-b", "secret b").unwrap();
+        store.delete("profile-b").unwrap();
+
+        let content = std::fs::read_to_string(dir.0.join(SECRETS_FILE_NAME)).unwrap();
+        assert!(!content.contains("secret a"));
+
+        let mut reopened = dir.store();
+        reopened.unlock("passphrase").unwrap();
+        assert_eq!(
+            reopened.get("profile-a").unwrap().as_deref(),
+            Some("secret a")
+        );
+        assert_eq!(reopened.get("profile-b").unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_wrong_passphrase() {
+        let dir = TempDir::new("wrong-passphrase");
+        let mut store = dir.store();
+        store.unlock("passphrase").unwrap();
+        store.set("profile", "secret").unwrap();
+
+        let mut reopened = dir.store();
+        assert_eq!(
+            reopened.unlock("not the passphrase"),
+            Err("Wrong master passphrase".to_string())
+        );
+        assert!(!reopened.is_unlocked());
+        assert!(reopened.get("profile").is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_file() {
+        let dir = TempDir::new("tampered");
+        let mut store = dir.store();
+        store.unlock("passphrase").unwrap();
+        store.set("profile", "secret").unwrap();
+
+        let path = dir.0.join(SECRETS_FILE_NAME);
+        let mut file: SecretsFile = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let mut ciphertext = BASE64.decode(&file.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        file.ciphertext = BASE64.encode(ciphertext);
+        std::fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        assert!(dir.store().unlock("passphrase").is_err());
+    }
+
+    #[test]
+    fn locked_store_refuses_access() {
+        let dir = TempDir::new("locked");
+        let mut store = dir.store();
+        assert!(store.get("profile").is_err());
+        assert!(store.set("profile", "secret").is_err());
+        assert!(store.unlock("").is_err());
+        assert!(!dir.0.join(SECRETS_FILE_NAME).exists());
+    }
+}
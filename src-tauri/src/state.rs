@@ -1,54 +1,225 @@
-use crate::config;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
 
+use etcd_client::Client;
+
+use crate::{config, election, lease, pool, secrets, watch};
+
+/// State shared by all commands.
+///
+/// Every part has its own lock, held only briefly and never across a network call, so
+/// that a slow request does not hold up the others.
 pub struct AppState {
-    pub app_config: config::AppConfig,
+    // Replaced as a whole on update, readers keep the snapshot they got
+    app_config: RwLock<Arc<config::AppConfig>>,
+
+    pub clients: Arc<pool::ClientPool>,
+
+    watches: Mutex<watch::Watches>,
+
+    lease_keep_alives: Mutex<lease::KeepAlives>,
 
-    pub etcd_client: Option<etcd_client::Client>,
+    election_observers: Mutex<election::Observers>,
+
+    // Also serializes config updates, which all go through it
+    secret_store: Mutex<Box<dyn secrets::SecretStore>>,
+}
+
+// A panic while holding one of the locks leaves consistent data behind, keep going
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 impl AppState {
     pub fn new(app_handle: &tauri::AppHandle) -> std::io::Result<Self> {
-        let app_config = config::AppConfig::from_file(
-            config::AppConfig::get_config_path(app_handle).map_err(|e| std::io::Error::other(e))?,
-        )?;
-        Ok(AppState {
-            app_config,
-            etcd_client: None,
+        let config_path =
+            config::AppConfig::get_config_path(app_handle).map_err(|e| std::io::Error::other(e))?;
+        let app_config =
+            config::AppConfig::from_file(&config_path).map_err(std::io::Error::other)?;
+        let secret_store =
+            secrets::open_store(config_path.parent().unwrap_or(std::path::Path::new(".")));
+        let state = AppState {
+            app_config: RwLock::new(Arc::new(app_config)),
+            secret_store: Mutex::new(secret_store),
+            ..Default::default()
+        };
+        if let Err(e) = state.load_secrets(&config_path) {
+            log::error!("Failed to load secrets: {}", e);
+        }
+        Ok(state)
+    }
+
+    /// A snapshot of the current config
+    pub fn config(&self) -> Arc<config::AppConfig> {
+        self.app_config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn watches(&self) -> MutexGuard<'_, watch::Watches> {
+        lock(&self.watches)
+    }
+
+    pub fn lease_keep_alives(&self) -> MutexGuard<'_, lease::KeepAlives> {
+        lock(&self.lease_keep_alives)
+    }
+
+    pub fn election_observers(&self) -> MutexGuard<'_, election::Observers> {
+        lock(&self.election_observers)
+    }
+
+    pub fn secret_store(&self) -> MutexGuard<'_, Box<dyn secrets::SecretStore>> {
+        lock(&self.secret_store)
+    }
+
+    /// Save `config` to `config_path` with its passwords moved to the secret store, then
+    /// make it the current config
+    pub fn save_config(
+        &self,
+        config: config::AppConfig,
+        config_path: &std::path::Path,
+    ) -> Result<(), String> {
+        self.update_config(config_path, |current| {
+            *current = config;
+            Ok(())
         })
     }
 
-    pub async fn init_client(&mut self) -> Result<bool, String> {
-        if self.etcd_client.is_some() {
-            return Ok(true);
+    /// Apply `update` to the current config and save it like [`AppState::save_config`].
+    ///
+    /// Updates run one at a time, so that concurrent ones do not overwrite each other.
+    pub fn update_config<T>(
+        &self,
+        config_path: &std::path::Path,
+        update: impl FnOnce(&mut config::AppConfig) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut secret_store = self.secret_store();
+        let previous = self.config();
+        let mut config = config::AppConfig::clone(&previous);
+        let result = update(&mut config)?;
+
+        let on_disk = config.store_secrets(&previous, secret_store.as_mut())?;
+        log::debug!("Updating config: {:?}", on_disk);
+        on_disk.save(config_path)?;
+        *self
+            .app_config
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+        drop(secret_store);
+
+        let current = self.config();
+        for profile in &previous.profiles {
+            let unchanged = current
+                .get_profile(&profile.name)
+                .is_some_and(|new| new.same_connection(profile));
+            if !unchanged {
+                log::info!("Profile {} was removed or changed", profile.name);
+                self.release_profile(&profile.name);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Resolve the profile passwords from the secret store, and move the ones still in
+    /// plaintext in the config file to it.
+    ///
+    /// Does nothing while the store is locked, plaintext passwords keep working meanwhile.
+    pub fn load_secrets(&self, config_path: &std::path::Path) -> Result<(), String> {
+        let mut secret_store = self.secret_store();
+        if !secret_store.is_unlocked() {
+            log::info!("Secret store is locked, profile passwords are not loaded yet");
+            return Ok(());
         }
+        let mut config = config::AppConfig::clone(&self.config());
+        config.resolve_secrets(secret_store.as_ref())?;
+
+        if config.has_plaintext_passwords() {
+            log::info!("Moving plaintext passwords from the config file to the secret store");
+            let previous = config.clone();
+            let on_disk = config.store_secrets(&previous, secret_store.as_mut())?;
+            on_disk.save(config_path)?;
+        }
+        *self
+            .app_config
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+        Ok(())
+    }
 
-        let Some(current_profile) = self.app_config.get_current_profile() else {
-            return Ok(false);
+    /// A session on `profile`, or on the current profile by default
+    pub fn session(&self, profile: Option<String>) -> Result<Session, String> {
+        let config = self.config();
+        let profile = match profile {
+            Some(name) => config
+                .get_profile(&name)
+                .ok_or_else(|| format!("Profile {} not found", name))?,
+            None => config
+                .get_current_profile()
+                .ok_or_else(|| "No current profile set".to_string())?,
         };
-        self.etcd_client = Some(crate::client::new_connect(&current_profile).await?);
+        Ok(Session {
+            profile: profile.clone(),
+            clients: self.clients.clone(),
+        })
+    }
 
-        Ok(true)
+    /// Forget the client and stop the background operations of a profile that was
+    /// removed, or whose connection settings changed
+    pub fn release_profile(&self, name: &str) {
+        self.clients.evict(name);
+        self.watches().clear_profile(name);
+        self.lease_keep_alives().clear_profile(name);
+        self.election_observers().clear_profile(name);
     }
+}
+
+/// What operations on one profile need, taken out of [`AppState`] so that they run
+/// without holding its locks
+#[derive(Clone)]
+pub struct Session {
+    // Snapshot of the profile when the session started
+    pub profile: config::Profile,
+    clients: Arc<pool::ClientPool>,
+}
 
-    pub async fn get_client(&mut self) -> Result<&mut etcd_client::Client, String> {
-        match self.init_client().await {
-            Ok(true) => (),
-            Ok(false) => return Err("Could not find current profile".to_string()),
-            Err(e) => return Err(e),
+impl Session {
+    /// Used by commands that may change etcd server data.
+    ///
+    /// Return Err if the profile is locked.
+    pub fn ensure_unlocked(&self) -> Result<(), String> {
+        if let Some(true) = self.profile.locked {
+            Err(format!("Profile {} is locked", self.profile.name))
+        } else {
+            Ok(())
         }
+    }
+
+    /// The pooled client of the profile, with the generation to pass to
+    /// [`Session::refresh_client`]
+    pub async fn client(&self) -> Result<(Client, u64), String> {
+        self.clients.get(&self.profile).await
+    }
+
+    /// Reconnect after the client of generation `stale` failed
+    pub async fn refresh_client(&self, stale: u64) -> Result<(Client, u64), String> {
+        self.clients.refresh(&self.profile, stale).await
+    }
 
-        Ok(self
-            .etcd_client
-            .as_mut()
-            .expect("Client should be initialized"))
+    /// Drop the pooled client of the profile, the next operation reconnects
+    pub fn reset_client(&self) {
+        self.clients.evict(&self.profile.name);
     }
 }
 
 impl Default for AppState {
     fn default() -> Self {
         AppState {
-            app_config: config::AppConfig::default(),
-            etcd_client: None,
+            app_config: RwLock::new(Arc::new(config::AppConfig::default())),
+            clients: Arc::new(pool::ClientPool::default()),
+            watches: Mutex::new(watch::Watches::default()),
+            lease_keep_alives: Mutex::new(lease::KeepAlives::default()),
+            election_observers: Mutex::new(election::Observers::default()),
+            secret_store: Mutex::new(Box::new(secrets::KeyringStore)),
         }
     }
 }
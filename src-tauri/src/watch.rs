@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use etcd_client::{EventType, WatchOptions, WatchStream, Watcher};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use tauri::async_runtime::JoinHandle;
+
+use crate::client::{Encoding, Item};
+
+pub const WATCH_EVENT: &str = "etcd-watch";
+
+/// Parameters of a watch requested by the UI
+#[derive(Deserialize, Debug)]
+pub struct WatchRequest {
+    pub key: String,
+    #[serde(default)]
+    pub key_encoding: Encoding,
+    // Watch every key starting with `key` instead of the key itself
+    #[serde(default)]
+    pub prefix: bool,
+    pub start_revision: Option<i64>,
+    #[serde(default)]
+    pub prev_kv: bool,
+}
+
+impl WatchRequest {
+    pub fn options(&self) -> WatchOptions {
+        let mut options = WatchOptions::new();
+        if self.prefix {
+            options = options.with_prefix();
+        }
+        if let Some(revision) = self.start_revision {
+            options = options.with_start_revision(revision);
+        }
+        if self.prev_kv {
+            options = options.with_prev_key();
+        }
+        options
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchEventType {
+    Put,
+    Delete,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WatchEventItem {
+    pub event_type: WatchEventType,
+    pub kv: Option<Item>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev_kv: Option<Item>,
+}
+
+/// Payload emitted on [`WATCH_EVENT`] for every response of a watch stream
+#[derive(Serialize, Clone, Debug)]
+pub struct WatchEvent {
+    pub watch_id: u64,
+    pub revision: i64,
+    pub events: Vec<WatchEventItem>,
+    // Last event of a watch that ended on its own, for the reason in `error`
+    pub canceled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct WatchHandle {
+    // Profile of the cluster being watched
+    profile: String,
+    watcher: Watcher,
+    task: JoinHandle<()>,
+}
+
+type WatchHandles = Arc<Mutex<HashMap<u64, WatchHandle>>>;
+
+/// Watches started by the UI, keyed by an ID local to this app
+#[derive(Default)]
+pub struct Watches {
+    next_id: u64,
+    // Shared with the forwarding tasks, which remove their watch when its stream ends
+    handles: WatchHandles,
+}
+
+impl Watches {
+    /// Forward the events of `stream` to the UI until the watch is stopped.
+    ///
+    /// Returns the ID used in the emitted [`WatchEvent`]s.
+    pub fn spawn(
+        &mut self,
+        app_handle: tauri::AppHandle,
+        profile: String,
+        watcher: Watcher,
+        stream: WatchStream,
+    ) -> u64 {
+        self.next_id += 1;
+        let watch_id = self.next_id;
+        // Hold the lock until the handle is in, in case the stream ends right away
+        let mut handles = lock(&self.handles);
+        let task = tauri::async_runtime::spawn(forward_events(
+            app_handle,
+            watch_id,
+            stream,
+            self.handles.clone(),
+        ));
+        handles.insert(
+            watch_id,
+            WatchHandle {
+                profile,
+                watcher,
+                task,
+            },
+        );
+        watch_id
+    }
+
+    /// Stop forwarding the events of a watch, and return its watcher to [`cancel`] it
+    pub fn stop(&mut self, watch_id: u64) -> Result<Watcher, String> {
+        let handle = lock(&self.handles)
+            .remove(&watch_id)
+            .ok_or_else(|| format!("Watch {watch_id} not found"))?;
+        handle.task.abort();
+        Ok(handle.watcher)
+    }
+
+    /// Drop the watches of a profile, e.g. when the client they were created on is replaced
+    pub fn clear_profile(&mut self, profile: &str) {
+        lock(&self.handles).retain(|watch_id, handle| {
+            if handle.profile != profile {
+                return true;
+            }
+            log::debug!("Stopping watch {watch_id}");
+            handle.task.abort();
+            false
+        });
+    }
+}
+
+/// Cancel a watch stopped with [`Watches::stop`] on the server
+pub async fn cancel(watch_id: u64, mut watcher: Watcher) {
+    if let Err(e) = watcher.cancel().await {
+        log::warn!("Failed to cancel watch {watch_id}: {e}");
+    }
+}
+
+fn lock(handles: &WatchHandles) -> std::sync::MutexGuard<'_, HashMap<u64, WatchHandle>> {
+    handles.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn emit_watch_event(app_handle: &tauri::AppHandle, payload: WatchEvent) {
+    if let Err(err) = app_handle.emit(WATCH_EVENT, payload) {
+        log::error!("Failed to emit watch event: {err}");
+    }
+}
+
+async fn forward_events(
+    app_handle: tauri::AppHandle,
+    watch_id: u64,
+    stream: WatchStream,
+    handles: WatchHandles,
+) {
+    let reason = forward_stream(&app_handle, watch_id, stream).await;
+    // Watches stopped on request are aborted before getting here, so this one ended
+    // on its own: forget it and let the UI know why
+    lock(&handles).remove(&watch_id);
+    emit_watch_event(
+        &app_handle,
+        WatchEvent {
+            watch_id,
+            revision: 0,
+            events: Vec::new(),
+            canceled: true,
+            error: Some(reason),
+        },
+    );
+}
+
+/// Emit the events of `stream` until it ends, returns why it did
+async fn forward_stream(
+    app_handle: &tauri::AppHandle,
+    watch_id: u64,
+    mut stream: WatchStream,
+) -> String {
+    loop {
+        match stream.message().await {
+            Ok(Some(response)) => {
+                let revision = response.header().map_or(0, |h| h.revision());
+                let events: Vec<_> = response
+                    .events()
+                    .iter()
+                    .map(|event| WatchEventItem {
+                        event_type: match event.event_type() {
+                            EventType::Put => WatchEventType::Put,
+                            EventType::Delete => WatchEventType::Delete,
+                        },
+                        kv: event.kv().cloned().map(Item::from),
+                        prev_kv: event.prev_kv().cloned().map(Item::from),
+                    })
+                    .collect();
+                let compacted = response.compact_revision() != 0;
+                // The final response only gets its own event if it carries changes
+                if !(compacted || response.canceled()) || !events.is_empty() {
+                    emit_watch_event(
+                        app_handle,
+                        WatchEvent {
+                            watch_id,
+                            revision,
+                            events,
+                            canceled: false,
+                            error: None,
+                        },
+                    );
+                }
+
+                if compacted {
+                    log::info!("Watch {watch_id} canceled by compaction");
+                    return format!(
+                        "Requested revision has been compacted, oldest available revision is {}",
+                        response.compact_revision()
+                    );
+                }
+                if response.canceled() {
+                    log::info!("Watch {watch_id} canceled");
+                    return match response.cancel_reason() {
+                        "" => "Canceled by the server".to_string(),
+                        reason => reason.to_string(),
+                    };
+                }
+            }
+            Ok(None) => {
+                log::info!("Watch {watch_id} stream closed");
+                return "Watch stream closed by the server".to_string();
+            }
+            Err(e) => {
+                log::error!("Watch {watch_id} failed: {e}");
+                return e.to_string();
+            }
+        }
+    }
+}
@@ -1,22 +1,39 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 
+/**
+ * How raw etcd bytes are represented as text
+ */
+export type Encoding = "utf8" | "base64" | "hex";
+
 /**
  * Represents a key-value pair from etcd
+ * Keys and values that are not valid UTF-8 are carried as base64
  */
 export interface EtcdItem {
     key: string;
     value: string;
+    key_encoding: Encoding;
+    value_encoding: Encoding;
     version: number;
     create_revision: number;
     mod_revision: number;
     lease: number;
 }
 
+/**
+ * A key without its value, as returned by keys-only listings
+ */
+export interface EtcdItemKey {
+    key: string;
+    key_encoding: Encoding;
+}
+
 /**
  * Application configuration interface
  */
 export interface AppConfig {
+    schema_version?: number; // Set by the backend, older config files are migrated on load
     profiles: Profile[];
     current_profile: string | null;
     color_theme: 'Light' | 'Dark' | 'System';
@@ -91,10 +108,22 @@ export interface Profile {
     name: string;
     endpoints: Endpoint[];
     user?: [string, string]; // [username, password] tuple
+    password_ref?: string; // Key of the password in the secret store, kept out of config.json
     timeout_ms?: number;
     connect_timeout_ms?: number;
     locked?: boolean;
-    metrics_path?: string;
+    metrics_path?: string; // No metrics when unset
+    tls?: TlsConfig;
+}
+
+/**
+ * PEM file paths used for TLS / mutual TLS connections
+ */
+export interface TlsConfig {
+    ca_cert_path?: string;
+    client_cert_path?: string;
+    client_key_path?: string;
+    server_name?: string;
 }
 
 export interface ParsedMetricSample {
@@ -111,10 +140,14 @@ export interface ParsedMetricFamily {
 
 /**
  * Connect to an etcd cluster with the specified connection info
+ *
+ * Clients are pooled per profile. Like every cluster command, this takes an optional
+ * `profile` to run against, the current profile by default.
+ * @param reconnect Drop the pooled client of the profile and connect again
  */
-export async function initializeEtcdClient(): Promise<boolean> {
+export async function initializeEtcdClient(profile?: string, reconnect?: boolean): Promise<boolean> {
     try {
-        return await invoke<boolean>('initialize_etcd_client');
+        return await invoke<boolean>('initialize_etcd_client', { profile, reconnect });
     } catch (error) {
         console.error('Error connecting to etcd:', error);
         throw error;
@@ -125,10 +158,10 @@ export async function initializeEtcdClient(): Promise<boolean> {
  * Fetch key-value pairs from etcd with the specified prefix
  * @param prefix The key prefix to filter by (default: '/')
  */
-export async function fetchEtcdItems(prefix: string = '/'): Promise<EtcdItem[]> {
+export async function fetchEtcdItems(prefix: string = '/', profile?: string): Promise<EtcdItem[]> {
     try {
         // Call the Rust list_items command with the specified prefix
-        const items = await invoke<EtcdItem[]>('list_items', { prefix });
+        const items = await invoke<EtcdItem[]>('list_items', { prefix, profile });
         return items;
     } catch (error) {
         console.error('Error fetching etcd items:', error);
@@ -139,9 +172,9 @@ export async function fetchEtcdItems(prefix: string = '/'): Promise<EtcdItem[]>
 /**
  * Fetch only keys by prefix (no values), for counts and pagination
  */
-export async function fetchEtcdKeysOnly(prefix: string = '/'): Promise<string[]> {
+export async function fetchEtcdKeysOnly(prefix: string = '/', profile?: string): Promise<EtcdItemKey[]> {
     try {
-        return await invoke<string[]>('list_keys_only', { prefix });
+        return await invoke<EtcdItemKey[]>('list_keys_only', { prefix, profile });
     } catch (error) {
         console.error('Error fetching etcd keys only:', error);
         throw error;
@@ -151,23 +184,59 @@ export async function fetchEtcdKeysOnly(prefix: string = '/'): Promise<string[]>
 /**
  * Fetch values in range [startKey, endKey] inclusive
  */
-export async function fetchValuesInRange(startKey: string, endKey: string): Promise<EtcdItem[]> {
+export async function fetchValuesInRange(startKey: EtcdItemKey, endKey: EtcdItemKey, profile?: string): Promise<EtcdItem[]> {
     try {
-        return await invoke<EtcdItem[]>('get_values_in_range', { startKey: startKey, endInclusive: endKey });
+        return await invoke<EtcdItem[]>('get_values_in_range', {
+            startKey: startKey.key,
+            endInclusive: endKey.key,
+            startKeyEncoding: startKey.key_encoding,
+            endKeyEncoding: endKey.key_encoding,
+            profile,
+        });
     } catch (error) {
         console.error('Error fetching values in range:', error);
         throw error;
     }
 }
 
+/**
+ * Optional parameters of putEtcdItem
+ */
+export interface PutOptions {
+    key_encoding?: Encoding;
+    value_encoding?: Encoding;
+    /** Attach the key to an existing lease */
+    lease_id?: number;
+    /** Attach the key to a new lease with this TTL in seconds */
+    ttl?: number;
+    /** Only write if the key is still at this revision, 0 if it must not exist */
+    expected_mod_revision?: number;
+    /** Write unconditionally, required when expected_mod_revision is not set */
+    overwrite?: boolean;
+}
+
+/**
+ * Error thrown by putEtcdItem when the key changed since it was loaded
+ */
+export interface PutConflict {
+    expected_mod_revision: number;
+    current: EtcdItem | null;
+}
+
+export function isPutConflict(error: unknown): error is PutConflict {
+    return typeof error === "object" && error !== null && "expected_mod_revision" in error;
+}
+
 /**
  * Put a key-value pair into etcd
  * @param key The key to add
  * @param value The value to add
+ * @param options Encodings, lease and compare-and-swap settings
+ * @throws PutConflict if expected_mod_revision does not match, a string otherwise
  */
-export async function putEtcdItem(key: string, value: string): Promise<void> {
+export async function putEtcdItem(key: string, value: string, options: PutOptions = {}, profile?: string): Promise<void> {
     try {
-        await invoke<void>('put_key', { key, value });
+        await invoke<void>('put_key', { key, value, options, profile });
     } catch (error) {
         console.error('Error adding etcd item:', error);
         throw error;
@@ -177,10 +246,11 @@ export async function putEtcdItem(key: string, value: string): Promise<void> {
 /**
  * Delete a key from etcd
  * @param key The key to delete
+ * @param keyEncoding How the key text is encoded (default: utf8)
  */
-export async function deleteEtcdItem(key: string): Promise<void> {
+export async function deleteEtcdItem(key: string, keyEncoding?: Encoding, profile?: string): Promise<void> {
     try {
-        await invoke<void>('delete_key', { key });
+        await invoke<void>('delete_key', { key, keyEncoding, profile });
     } catch (error) {
         console.error('Error deleting etcd item:', error);
         throw error;
@@ -190,9 +260,9 @@ export async function deleteEtcdItem(key: string): Promise<void> {
 /**
  * Fetch Prometheus format metrics from etcd
  */
-export async function fetchMetrics(endpoint: Endpoint): Promise<ParsedMetricFamily[]> {
+export async function fetchMetrics(endpoint: Endpoint, profile?: string): Promise<ParsedMetricFamily[]> {
     try {
-        return await invoke<ParsedMetricFamily[]>('fetch_metrics', { endpoint });
+        return await invoke<ParsedMetricFamily[]>('fetch_metrics', { endpoint, profile });
     } catch (error) {
         console.error('Error fetching metrics:', error);
         throw error;
@@ -305,6 +375,94 @@ export async function testConnection(profile: Profile): Promise<string> {
     return await invoke<string>('test_connection', { profile });
 }
 
+export type SecretBackend = "keyring" | "encrypted_file";
+
+export interface SecretStoreStatus {
+    backend: SecretBackend;
+    /** The encrypted-file store needs the master passphrase before passwords are usable */
+    unlocked: boolean;
+}
+
+export async function getSecretStoreStatus(): Promise<SecretStoreStatus> {
+    try {
+        return await invoke<SecretStoreStatus>('get_secret_store_status');
+    } catch (error) {
+        console.error('Error getting secret store status:', error);
+        throw error;
+    }
+}
+
+/**
+ * Unlock the encrypted-file secret store, the first unlock sets the master passphrase.
+ * Reload the config afterwards to get the profile passwords.
+ */
+export async function unlockSecretStore(passphrase: string): Promise<void> {
+    try {
+        await invoke<void>('unlock_secret_store', { passphrase });
+    } catch (error) {
+        console.error('Error unlocking secret store:', error);
+        throw error;
+    }
+}
+
+export type NameCollision = "skip" | "rename" | "overwrite";
+
+export interface ProfileImportResult {
+    /** Names the profiles were imported under */
+    imported: string[];
+    skipped: string[];
+    overwritten: string[];
+}
+
+/**
+ * A profile built from etcdctl settings, to review before saving
+ */
+export interface ProfileDraft {
+    profile: Profile;
+    /** Settings that were ignored or could not be converted */
+    warnings: string[];
+}
+
+/**
+ * Write profiles to a portable file
+ * @param includeSecrets Include the passwords, otherwise only usernames are exported
+ * @returns The number of exported profiles
+ */
+export async function exportProfiles(names: string[], path: string, includeSecrets: boolean): Promise<number> {
+    try {
+        return await invoke<number>('export_profiles', { names, path, includeSecrets });
+    } catch (error) {
+        console.error('Error exporting profiles:', error);
+        throw error;
+    }
+}
+
+/**
+ * Add the profiles of a file written by exportProfiles
+ * @param collision What to do when a profile name is taken, renames by default
+ */
+export async function importProfiles(path: string, collision?: NameCollision): Promise<ProfileImportResult> {
+    try {
+        return await invoke<ProfileImportResult>('import_profiles', { path, collision });
+    } catch (error) {
+        console.error('Error importing profiles:', error);
+        throw error;
+    }
+}
+
+/**
+ * Build a draft profile from ETCDCTL_* variables
+ * @param envText KEY=value lines, such as the output of `env | grep ETCDCTL`; the app's own environment if omitted
+ */
+export async function profileFromEtcdctlEnv(envText?: string): Promise<ProfileDraft> {
+    try {
+        return await invoke<ProfileDraft>('profile_from_etcdctl_env', { envText });
+    } catch (error) {
+        console.error('Error building profile from etcdctl environment:', error);
+        throw error;
+    }
+}
+
 /**
  * Save a path to the history
  * @param path The path to save
@@ -365,9 +523,51 @@ export async function openDevtools(): Promise<void> {
  */
 export interface MemberInfo {
     id: number;
+    /** Exact member ID in hex, as printed by etcdctl; `id` loses precision */
+    hex_id: string;
     name: string;
     peer_urls: string[];
     client_urls: string[];
+    is_learner: boolean;
+}
+
+/**
+ * Status reported by a member itself
+ */
+export interface EndpointStatus {
+    version: string;
+    db_size: number;
+    db_size_in_use: number;
+    leader: number;
+    raft_index: number;
+    raft_term: number;
+    raft_applied_index: number;
+    is_learner: boolean;
+    /** Alarms and other errors reported by the member */
+    errors: string[];
+}
+
+export interface MemberStatus {
+    member_id: number;
+    hex_id: string;
+    /** Client URL the status was read from */
+    endpoint: string | null;
+    /** Missing if the member could not be reached, see `error` */
+    status: EndpointStatus | null;
+    error?: string;
+}
+
+/**
+ * Alarm raised by a member, writes are refused until it is disarmed
+ * - nospace: the member's backend exceeded its space quota
+ * - corrupt: the member's data is inconsistent with the rest of the cluster
+ */
+export type AlarmKind = 'nospace' | 'corrupt';
+
+export interface AlarmInfo {
+    member_id: number;
+    hex_id: string;
+    alarm: AlarmKind;
 }
 
 /**
@@ -382,20 +582,219 @@ export interface ClusterInfo {
     raft_term: number;
     leader: number;
     members: MemberInfo[];
+    member_statuses: MemberStatus[];
+    alarms: AlarmInfo[];
+    /** Set when the alarms could not be listed, e.g. for non-admin users */
+    alarms_error?: string;
 }
 
 /**
  * Get cluster information including members and status
  */
-export async function getClusterInfo(): Promise<ClusterInfo> {
+export async function getClusterInfo(profile?: string): Promise<ClusterInfo> {
     try {
-        return await invoke<ClusterInfo>('get_cluster_info');
+        return await invoke<ClusterInfo>('get_cluster_info', { profile });
     } catch (error) {
         console.error('Error getting cluster info:', error);
         throw error;
     }
 }
 
+/**
+ * Add a member, optionally as a non-voting learner. Returns the refreshed member list
+ */
+export async function addMember(peerUrls: string[], isLearner: boolean = false, profile?: string): Promise<MemberInfo[]> {
+    try {
+        return await invoke<MemberInfo[]>('add_member', { peerUrls, isLearner, profile });
+    } catch (error) {
+        console.error('Error adding member:', error);
+        throw error;
+    }
+}
+
+/**
+ * Remove a member by its hex ID. Returns the refreshed member list
+ */
+export async function removeMember(memberId: string, profile?: string): Promise<MemberInfo[]> {
+    try {
+        return await invoke<MemberInfo[]>('remove_member', { memberId, profile });
+    } catch (error) {
+        console.error('Error removing member:', error);
+        throw error;
+    }
+}
+
+/**
+ * Replace the peer URLs of a member. Returns the refreshed member list
+ */
+export async function updateMember(memberId: string, peerUrls: string[], profile?: string): Promise<MemberInfo[]> {
+    try {
+        return await invoke<MemberInfo[]>('update_member', { memberId, peerUrls, profile });
+    } catch (error) {
+        console.error('Error updating member:', error);
+        throw error;
+    }
+}
+
+/**
+ * Promote a learner to a voting member. Returns the refreshed member list
+ */
+export async function promoteMember(memberId: string, profile?: string): Promise<MemberInfo[]> {
+    try {
+        return await invoke<MemberInfo[]>('promote_member', { memberId, profile });
+    } catch (error) {
+        console.error('Error promoting member:', error);
+        throw error;
+    }
+}
+
+/**
+ * State of a member before or after a maintenance operation
+ */
+export interface MaintenanceStatus {
+    /** Hex IDs, as printed by etcdctl */
+    member_id: string;
+    leader_id: string;
+    revision: number;
+    db_size: number;
+    db_size_in_use: number;
+    raft_applied_index: number;
+}
+
+/**
+ * Outcome of a maintenance operation, `after` is only set if it actually ran
+ */
+export interface MaintenanceResult {
+    dry_run: boolean;
+    before: MaintenanceStatus;
+    after: MaintenanceStatus | null;
+}
+
+export interface MemberHash {
+    member_id: string;
+    hash: number | null;
+    revision: number;
+    compact_revision: number;
+    /** Compacted at another revision than the other members, the hash is not comparable */
+    compact_revision_differs: boolean;
+    error?: string;
+}
+
+export interface HashKvResult {
+    /** Every member answered with a comparable hash, otherwise consistency is unknown */
+    complete: boolean;
+    /** Every member has the same hash, never true unless `complete` */
+    consistent: boolean;
+    members: MemberHash[];
+}
+
+/**
+ * Defragment a single member, which blocks it while running
+ */
+export async function defragmentMember(memberId: string, dryRun: boolean = false, profile?: string): Promise<MaintenanceResult> {
+    try {
+        return await invoke<MaintenanceResult>('defragment_member', { memberId, dryRun, profile });
+    } catch (error) {
+        console.error('Error defragmenting member:', error);
+        throw error;
+    }
+}
+
+/**
+ * Discard the history of every key before `revision`
+ */
+export async function compact(revision: number, physical: boolean = false, dryRun: boolean = false, profile?: string): Promise<MaintenanceResult> {
+    try {
+        return await invoke<MaintenanceResult>('compact', { revision, physical, dryRun, profile });
+    } catch (error) {
+        console.error('Error compacting:', error);
+        throw error;
+    }
+}
+
+/**
+ * Hand leadership over to another voting member
+ */
+export async function moveLeader(memberId: string, dryRun: boolean = false, profile?: string): Promise<MaintenanceResult> {
+    try {
+        return await invoke<MaintenanceResult>('move_leader', { memberId, dryRun, profile });
+    } catch (error) {
+        console.error('Error moving leader:', error);
+        throw error;
+    }
+}
+
+/**
+ * Hash the key-value store of every member at the same revision (current one by default)
+ */
+export async function hashKv(revision?: number, profile?: string): Promise<HashKvResult> {
+    try {
+        return await invoke<HashKvResult>('hash_kv', { revision, profile });
+    } catch (error) {
+        console.error('Error hashing members:', error);
+        throw error;
+    }
+}
+
+export interface SnapshotProgress {
+    received: number;
+    /** Total size announced by the member, 0 if unknown */
+    total: number;
+}
+
+export interface SnapshotResult {
+    path: string;
+    member_id: string;
+    size: number;
+    /** SHA-256 of the whole file */
+    sha256: string;
+    revision: number;
+}
+
+/**
+ * Save a snapshot of a member's database to a file, reporting progress through `listenSnapshotProgress`
+ */
+export async function snapshotMember(memberId: string, path: string, profile?: string): Promise<SnapshotResult> {
+    try {
+        return await invoke<SnapshotResult>('snapshot_member', { memberId, path, profile });
+    } catch (error) {
+        console.error('Error saving snapshot:', error);
+        throw error;
+    }
+}
+
+export async function listenSnapshotProgress(
+    handler: (payload: SnapshotProgress) => void,
+): Promise<() => void> {
+    return listen<SnapshotProgress>('snapshot-progress', (event) => {
+        handler(event.payload);
+    });
+}
+
+/**
+ * List the alarms active on any member
+ */
+export async function listAlarms(profile?: string): Promise<AlarmInfo[]> {
+    try {
+        return await invoke<AlarmInfo[]>('list_alarms', { profile });
+    } catch (error) {
+        console.error('Error listing alarms:', error);
+        throw error;
+    }
+}
+
+/**
+ * Disarm an alarm of a member, returns the alarms still active
+ */
+export async function disarmAlarm(memberId: string, alarm: AlarmKind, profile?: string): Promise<AlarmInfo[]> {
+    try {
+        return await invoke<AlarmInfo[]>('disarm_alarm', { memberId, alarm, profile });
+    } catch (error) {
+        console.error('Error disarming alarm:', error);
+        throw error;
+    }
+}
+
 /**
  * Get list of available system fonts
  */
@@ -412,10 +811,11 @@ export async function getSystemFonts(): Promise<string[]> {
  * Get a key's value at a specific revision
  * @param key The key to fetch
  * @param revision The revision to fetch at
+ * @param keyEncoding How the key text is encoded (default: utf8)
  */
-export async function getKeyAtRevision(key: string, revision: number): Promise<EtcdItem | null> {
+export async function getKeyAtRevision(key: string, revision: number, keyEncoding?: Encoding, profile?: string): Promise<EtcdItem | null> {
     try {
-        return await invoke<EtcdItem | null>('get_key_at_revision', { key, revision });
+        return await invoke<EtcdItem | null>('get_key_at_revision', { key, revision, keyEncoding, profile });
     } catch (error) {
         console.error('Error getting key at revision:', error);
         throw error;
@@ -438,3 +838,653 @@ export async function formatTimestamp(timestamp: number): Promise<{ utc: string;
         throw error;
     }
 }
+
+/**
+ * Parameters for watching a key or prefix
+ */
+export interface WatchRequest {
+    key: string;
+    key_encoding?: Encoding;
+    prefix?: boolean;
+    start_revision?: number;
+    prev_kv?: boolean;
+}
+
+export interface WatchEventItem {
+    event_type: "put" | "delete";
+    kv: EtcdItem | null;
+    prev_kv?: EtcdItem;
+}
+
+/**
+ * Payload of the 'etcd-watch' event, emitted for every response of a watch
+ */
+export interface WatchEvent {
+    watch_id: number;
+    revision: number;
+    events: WatchEventItem[];
+    canceled: boolean; // Last event of a watch that ended on its own, the reason is in error
+    error?: string;
+}
+
+/**
+ * Start watching a key or prefix
+ * @returns The watch ID carried by the emitted events
+ */
+export async function startWatch(request: WatchRequest, profile?: string): Promise<number> {
+    try {
+        return await invoke<number>('start_watch', { request, profile });
+    } catch (error) {
+        console.error('Error starting watch:', error);
+        throw error;
+    }
+}
+
+/**
+ * Stop a watch started with startWatch
+ */
+export async function stopWatch(watchId: number): Promise<void> {
+    try {
+        await invoke<void>('stop_watch', { watchId });
+    } catch (error) {
+        console.error('Error stopping watch:', error);
+        throw error;
+    }
+}
+
+export async function listenWatchEvents(
+    handler: (payload: WatchEvent) => void,
+): Promise<() => void> {
+    return listen<WatchEvent>('etcd-watch', (event) => {
+        handler(event.payload);
+    });
+}
+
+/**
+ * Details of a single lease
+ */
+export interface LeaseInfo {
+    id: number;
+    ttl: number; // -1 if the lease has expired
+    granted_ttl: number;
+    keys: EtcdItemKey[];
+}
+
+export interface LeaseKeepAliveEvent {
+    /** Lease IDs are only unique within the cluster of this profile */
+    profile: string;
+    lease_id: number;
+    ttl: number;
+    error?: string;
+}
+
+export async function listLeases(profile?: string): Promise<number[]> {
+    try {
+        return await invoke<number[]>('list_leases', { profile });
+    } catch (error) {
+        console.error('Error listing leases:', error);
+        throw error;
+    }
+}
+
+export async function getLeaseInfo(leaseId: number, profile?: string): Promise<LeaseInfo> {
+    try {
+        return await invoke<LeaseInfo>('get_lease_info', { leaseId, profile });
+    } catch (error) {
+        console.error('Error getting lease info:', error);
+        throw error;
+    }
+}
+
+/**
+ * Grant a new lease
+ * @param ttl TTL in seconds
+ * @param leaseId Requested lease ID, chosen by the server if omitted
+ * @returns The lease ID
+ */
+export async function grantLease(ttl: number, leaseId?: number, profile?: string): Promise<number> {
+    try {
+        return await invoke<number>('grant_lease', { ttl, leaseId, profile });
+    } catch (error) {
+        console.error('Error granting lease:', error);
+        throw error;
+    }
+}
+
+export async function revokeLease(leaseId: number, profile?: string): Promise<void> {
+    try {
+        await invoke<void>('revoke_lease', { leaseId, profile });
+    } catch (error) {
+        console.error('Error revoking lease:', error);
+        throw error;
+    }
+}
+
+/**
+ * Refresh a lease once
+ * @returns The new TTL in seconds
+ */
+export async function keepAliveLease(leaseId: number, profile?: string): Promise<number> {
+    try {
+        return await invoke<number>('keep_alive_lease', { leaseId, profile });
+    } catch (error) {
+        console.error('Error keeping lease alive:', error);
+        throw error;
+    }
+}
+
+export async function startLeaseKeepAlive(leaseId: number, profile?: string): Promise<void> {
+    try {
+        await invoke<void>('start_lease_keep_alive', { leaseId, profile });
+    } catch (error) {
+        console.error('Error starting lease keep-alive:', error);
+        throw error;
+    }
+}
+
+export async function stopLeaseKeepAlive(leaseId: number, profile?: string): Promise<void> {
+    try {
+        await invoke<void>('stop_lease_keep_alive', { leaseId, profile });
+    } catch (error) {
+        console.error('Error stopping lease keep-alive:', error);
+        throw error;
+    }
+}
+
+export async function listenLeaseKeepAliveEvents(
+    handler: (payload: LeaseKeepAliveEvent) => void,
+): Promise<() => void> {
+    return listen<LeaseKeepAliveEvent>('lease-keep-alive', (event) => {
+        handler(event.payload);
+    });
+}
+
+export type TxnCompareOp = "equal" | "not_equal" | "greater" | "less";
+
+export type TxnCompareTarget =
+    | { target: "value"; value: string; value_encoding?: Encoding }
+    | { target: "version"; version: number }
+    | { target: "create_revision"; revision: number }
+    | { target: "mod_revision"; revision: number }
+    | { target: "lease"; lease: number };
+
+export type TxnCompare = {
+    key: string;
+    key_encoding?: Encoding;
+    /** Compare every key starting with key */
+    prefix?: boolean;
+    op: TxnCompareOp;
+} & TxnCompareTarget;
+
+export type TxnRequestOp =
+    | { type: "put"; key: string; key_encoding?: Encoding; value: string; value_encoding?: Encoding; lease?: number; prev_kv?: boolean }
+    | { type: "delete"; key: string; key_encoding?: Encoding; prefix?: boolean; prev_kv?: boolean }
+    | { type: "get"; key: string; key_encoding?: Encoding; prefix?: boolean };
+
+/**
+ * A transaction: success ops run if every compare holds, failure ops otherwise
+ */
+export interface TxnRequest {
+    compare: TxnCompare[];
+    success: TxnRequestOp[];
+    failure: TxnRequestOp[];
+}
+
+export type TxnOpResult =
+    | { type: "put"; prev_kv: EtcdItem | null }
+    | { type: "delete"; deleted: number; prev_kvs: EtcdItem[] }
+    | { type: "get"; kvs: EtcdItem[]; count: number };
+
+export interface TxnResult {
+    succeeded: boolean;
+    revision: number;
+    responses: TxnOpResult[];
+}
+
+/**
+ * Run a multi-key transaction atomically
+ */
+export async function executeTxn(request: TxnRequest, profile?: string): Promise<TxnResult> {
+    try {
+        return await invoke<TxnResult>('execute_txn', { request, profile });
+    } catch (error) {
+        console.error('Error executing transaction:', error);
+        throw error;
+    }
+}
+
+/**
+ * Versions of a key, newest first
+ */
+export interface KeyHistory {
+    versions: EtcdItem[];
+    /** Older versions exist but have been compacted away */
+    compacted: boolean;
+    /** Stopped early because the requested limit was reached */
+    truncated: boolean;
+}
+
+/**
+ * Walk a key's history backwards from its latest (or the given) revision
+ * @param key The key to fetch
+ * @param options Key encoding, starting revision and maximum number of versions
+ */
+export async function getKeyHistory(
+    key: string,
+    options: { keyEncoding?: Encoding; fromRevision?: number; limit?: number } = {},
+    profile?: string,
+): Promise<KeyHistory> {
+    try {
+        return await invoke<KeyHistory>('get_key_history', { key, ...options, profile });
+    } catch (error) {
+        console.error('Error getting key history:', error);
+        throw error;
+    }
+}
+
+/**
+ * Optional parameters of deletePrefix and deleteRange
+ */
+export interface DeleteRangeOptions {
+    key_encoding?: Encoding;
+    /** Only count the keys and return a sample of them */
+    dry_run?: boolean;
+    /** Return the deleted entries so they can be restored */
+    prev_kv?: boolean;
+    /** Number of keys returned by a dry run (default: 20) */
+    sample_size?: number;
+}
+
+export interface DeleteRangeResult {
+    dry_run: boolean;
+    count: number;
+    sample: EtcdItemKey[];
+    prev_kvs: EtcdItem[];
+}
+
+/**
+ * Delete every key with the specified prefix
+ */
+export async function deletePrefix(prefix: string, options: DeleteRangeOptions = {}, profile?: string): Promise<DeleteRangeResult> {
+    try {
+        return await invoke<DeleteRangeResult>('delete_prefix', { prefix, options, profile });
+    } catch (error) {
+        console.error('Error deleting prefix:', error);
+        throw error;
+    }
+}
+
+/**
+ * Delete every key in range [startKey, endKey] inclusive
+ */
+export async function deleteRange(startKey: string, endKey: string, options: DeleteRangeOptions = {}, profile?: string): Promise<DeleteRangeResult> {
+    try {
+        return await invoke<DeleteRangeResult>('delete_range', { startKey, endInclusive: endKey, options, profile });
+    } catch (error) {
+        console.error('Error deleting range:', error);
+        throw error;
+    }
+}
+
+/**
+ * File formats for exported keys:
+ * - json: items with their metadata
+ * - yaml: flat `key: value` mapping, binary data tagged `!!binary`
+ * - etcdctl: same shape as `etcdctl get -w json`
+ */
+export type DumpFormat = 'json' | 'yaml' | 'etcdctl';
+
+export interface ExportResult {
+    path: string;
+    count: number;
+    /** Revision the whole dump was read at */
+    revision: number;
+}
+
+/**
+ * Export every key with the specified prefix to a file
+ */
+export async function exportPrefix(prefix: string, path: string, format: DumpFormat, keyEncoding?: Encoding, profile?: string): Promise<ExportResult> {
+    try {
+        return await invoke<ExportResult>('export_prefix', { prefix, path, format, keyEncoding, profile });
+    } catch (error) {
+        console.error('Error exporting prefix:', error);
+        throw error;
+    }
+}
+
+/**
+ * Export every key in range [startKey, endKey] inclusive to a file
+ */
+export async function exportRange(startKey: string, endKey: string, path: string, format: DumpFormat, keyEncoding?: Encoding, profile?: string): Promise<ExportResult> {
+    try {
+        return await invoke<ExportResult>('export_range', { startKey, endInclusive: endKey, path, format, keyEncoding, profile });
+    } catch (error) {
+        console.error('Error exporting range:', error);
+        throw error;
+    }
+}
+
+/**
+ * What to do with imported keys that already exist:
+ * - skip: keep the existing value
+ * - overwrite: replace the existing value
+ * - fail_if_different: abort unless the existing value is identical
+ */
+export type ConflictPolicy = 'skip' | 'overwrite' | 'fail_if_different';
+
+export interface ImportOptions {
+    policy?: ConflictPolicy;
+    /** Replace this prefix of every imported key with `rewrite_to` */
+    rewrite_from?: string;
+    rewrite_to?: string;
+}
+
+export interface ImportProgress {
+    total: number;
+    processed: number;
+    written: number;
+    skipped: number;
+}
+
+/**
+ * Load a dump into the current profile, reporting progress through `listenImportProgress`
+ */
+export async function importKeys(path: string, format: DumpFormat, options: ImportOptions = {}, profile?: string): Promise<ImportProgress> {
+    try {
+        return await invoke<ImportProgress>('import_keys', { path, format, options, profile });
+    } catch (error) {
+        console.error('Error importing keys:', error);
+        throw error;
+    }
+}
+
+export async function listenImportProgress(
+    handler: (payload: ImportProgress) => void,
+): Promise<() => void> {
+    return listen<ImportProgress>('import-progress', (event) => {
+        handler(event.payload);
+    });
+}
+
+/**
+ * One side of a diff: a prefix on a profile
+ */
+export interface DiffSide {
+    profile: string;
+    prefix: string;
+    key_encoding?: Encoding;
+}
+
+export interface ChangedKey {
+    left: EtcdItem;
+    right: EtcdItem;
+}
+
+export interface DiffResult {
+    /** Keys only on the right side */
+    added: EtcdItem[];
+    /** Keys only on the left side */
+    removed: EtcdItem[];
+    changed: ChangedKey[];
+    unchanged: number;
+}
+
+/**
+ * Compare two prefixes, possibly on two different profiles.
+ * With `ignorePrefix` (the default), keys are matched on what follows their prefix.
+ */
+export async function diffPrefixes(left: DiffSide, right: DiffSide, ignorePrefix: boolean = true): Promise<DiffResult> {
+    try {
+        return await invoke<DiffResult>('diff_prefixes', { left, right, ignorePrefix });
+    } catch (error) {
+        console.error('Error diffing prefixes:', error);
+        throw error;
+    }
+}
+
+export interface SyncOptions {
+    /** Only sync these keys, relative to the source prefix */
+    keys?: string[];
+    /** Only report what would be copied and deleted */
+    dry_run?: boolean;
+    /** Delete keys under the target prefix that are not in the source */
+    delete_extraneous?: boolean;
+    /** Attach copied keys to a lease with the same remaining TTL as in the source */
+    preserve_lease?: boolean;
+}
+
+export interface SyncResult {
+    dry_run: boolean;
+    copied: EtcdItemKey[];
+    deleted: EtcdItemKey[];
+    unchanged: number;
+}
+
+/**
+ * Copy a prefix from one profile to another, only writing keys that differ
+ */
+export async function syncPrefix(source: DiffSide, target: DiffSide, options: SyncOptions = {}): Promise<SyncResult> {
+    try {
+        return await invoke<SyncResult>('sync_prefix', { source, target, options });
+    } catch (error) {
+        console.error('Error syncing prefix:', error);
+        throw error;
+    }
+}
+
+export interface UserInfo {
+    name: string;
+    roles: string[];
+}
+
+export type PermissionKind = 'read' | 'write' | 'readwrite';
+
+/**
+ * A permission granted to a role, on a single key or a range of keys
+ */
+export interface PermissionInfo {
+    perm_type: PermissionKind;
+    key: string;
+    key_encoding: Encoding;
+    /** Empty for a single key */
+    range_end: string;
+    range_end_encoding: Encoding;
+    /** The range covers exactly the keys starting with `key` */
+    prefix: boolean;
+}
+
+export interface RoleInfo {
+    name: string;
+    permissions: PermissionInfo[];
+}
+
+/**
+ * A permission to grant or revoke. `range_end` is exclusive and ignored with `prefix`
+ */
+export interface PermissionRequest {
+    perm_type: PermissionKind;
+    key: string;
+    key_encoding?: Encoding;
+    prefix?: boolean;
+    range_end?: string;
+    range_end_encoding?: Encoding;
+}
+
+async function invokeAuth<T>(command: string, args: Record<string, unknown>, description: string): Promise<T> {
+    try {
+        return await invoke<T>(command, args);
+    } catch (error) {
+        console.error(`Error ${description}:`, error);
+        throw error;
+    }
+}
+
+export async function listUsers(profile?: string): Promise<UserInfo[]> {
+    return invokeAuth<UserInfo[]>('list_users', { profile }, 'listing users');
+}
+
+/**
+ * Add a user, without a password it can only authenticate with a client certificate
+ */
+export async function addUser(name: string, password?: string, profile?: string): Promise<void> {
+    return invokeAuth<void>('add_user', { name, password, profile }, 'adding user');
+}
+
+export async function deleteUser(name: string, profile?: string): Promise<void> {
+    return invokeAuth<void>('delete_user', { name, profile }, 'deleting user');
+}
+
+export async function changePassword(name: string, password: string, profile?: string): Promise<void> {
+    return invokeAuth<void>('change_password', { name, password, profile }, 'changing password');
+}
+
+export async function grantRole(user: string, role: string, profile?: string): Promise<void> {
+    return invokeAuth<void>('grant_role', { user, role, profile }, 'granting role');
+}
+
+export async function revokeRole(user: string, role: string, profile?: string): Promise<void> {
+    return invokeAuth<void>('revoke_role', { user, role, profile }, 'revoking role');
+}
+
+export async function listRoles(profile?: string): Promise<RoleInfo[]> {
+    return invokeAuth<RoleInfo[]>('list_roles', { profile }, 'listing roles');
+}
+
+export async function addRole(name: string, profile?: string): Promise<void> {
+    return invokeAuth<void>('add_role', { name, profile }, 'adding role');
+}
+
+export async function deleteRole(name: string, profile?: string): Promise<void> {
+    return invokeAuth<void>('delete_role', { name, profile }, 'deleting role');
+}
+
+export async function grantPermission(role: string, permission: PermissionRequest, profile?: string): Promise<void> {
+    return invokeAuth<void>('grant_permission', { role, permission, profile }, 'granting permission');
+}
+
+export async function revokePermission(role: string, permission: PermissionRequest, profile?: string): Promise<void> {
+    return invokeAuth<void>('revoke_permission', { role, permission, profile }, 'revoking permission');
+}
+
+/**
+ * Enable authentication, refused until a `root` user with the `root` role exists
+ * and the current profile has credentials
+ */
+export async function enableAuth(profile?: string): Promise<void> {
+    return invokeAuth<void>('enable_auth', { profile }, 'enabling authentication');
+}
+
+export async function disableAuth(profile?: string): Promise<void> {
+    return invokeAuth<void>('disable_auth', { profile }, 'disabling authentication');
+}
+
+export interface PermissionGrant {
+    role: string;
+    permission: PermissionInfo;
+}
+
+/**
+ * What a user may do on a key, and which roles allow it
+ */
+export interface EffectivePermission {
+    user: string;
+    roles: string[];
+    /** The root role bypasses every permission */
+    is_root: boolean;
+    read: boolean;
+    write: boolean;
+    grants: PermissionGrant[];
+}
+
+export async function checkPermission(user: string, key: string, keyEncoding?: Encoding, profile?: string): Promise<EffectivePermission> {
+    return invokeAuth<EffectivePermission>('check_permission', { user, key, keyEncoding, profile }, 'checking permission');
+}
+
+export interface Contender {
+    /** The value is the proposal of an election candidate, empty for locks */
+    item: EtcdItem;
+    /** Remaining TTL of the contender's lease, -1 if it has expired */
+    lease_ttl: number | null;
+}
+
+/**
+ * Holder and waiters of a lock or an election, waiters in queue order
+ */
+export interface Contenders {
+    holder: Contender | null;
+    waiters: Contender[];
+}
+
+export interface ElectionLeaderEvent {
+    observe_id: number;
+    leader: EtcdItem | null;
+    /** Last event of an observation that ended on its own, failed if `error` is set. Stopping an observer sends no event */
+    ended: boolean;
+    error?: string;
+}
+
+export async function listContenders(name: string, keyEncoding?: Encoding, profile?: string): Promise<Contenders> {
+    try {
+        return await invoke<Contenders>('list_contenders', { name, keyEncoding, profile });
+    } catch (error) {
+        console.error('Error listing contenders:', error);
+        throw error;
+    }
+}
+
+/**
+ * Start following the leader of an election
+ * @returns The observer ID, found in the emitted leader events
+ */
+export async function startElectionObserve(name: string, keyEncoding?: Encoding, profile?: string): Promise<number> {
+    try {
+        return await invoke<number>('start_election_observe', { name, keyEncoding, profile });
+    } catch (error) {
+        console.error('Error observing election:', error);
+        throw error;
+    }
+}
+
+export async function stopElectionObserve(observeId: number): Promise<void> {
+    try {
+        await invoke<void>('stop_election_observe', { observeId });
+    } catch (error) {
+        console.error('Error stopping election observer:', error);
+        throw error;
+    }
+}
+
+export interface ForceReleaseResult {
+    holder: Contender;
+    /** Every key attached to the holder's lease, all deleted along with the lock key */
+    deleted_keys: EtcdItemKey[];
+}
+
+/**
+ * Revoke the lease of the holder of a lock or an election, so that the next waiter takes over.
+ *
+ * Revoking the lease deletes every key attached to it, not only the lock key.
+ * @param expectedHolder Key of the holder to release, fails if another one holds the lock by now
+ */
+export async function forceReleaseLock(
+    name: string,
+    keyEncoding?: Encoding,
+    expectedHolder?: string,
+    profile?: string,
+): Promise<ForceReleaseResult> {
+    try {
+        return await invoke<ForceReleaseResult>('force_release_lock', { name, keyEncoding, expectedHolder, profile });
+    } catch (error) {
+        console.error('Error force-releasing lock:', error);
+        throw error;
+    }
+}
+
+export async function listenElectionLeaderEvents(
+    handler: (payload: ElectionLeaderEvent) => void,
+): Promise<() => void> {
+    return listen<ElectionLeaderEvent>('election-leader', (event) => {
+        handler(event.payload);
+    });
+}
@@ -1,5 +1,5 @@
 import { useState, useEffect, ChangeEvent } from "react";
-import { putEtcdItem } from "../../api/etcd";
+import { isPutConflict, putEtcdItem } from "../../api/etcd";
 import { Button, CloseButton, Dialog, Field, Input, Textarea, VStack } from "@chakra-ui/react";
 import { codeInputProps } from "@/utils/inputProps";
 
@@ -22,7 +22,10 @@ function AddKeyDialog({
     const [dialogNewKey, setDialogNewKey] = useState(defaultKeyPrefix);
     const [dialogNewValue, setDialogNewValue] = useState("");
     const { mutateAsync, isPending } = useMutation<void, String, { key: string, value: string }>({
-        mutationFn: async ({ key, value }) => await putEtcdItem(key, value),
+        // Never replace an existing key from here
+        mutationFn: async ({ key, value }) => await putEtcdItem(key, value, { expected_mod_revision: 0 }).catch((error) => {
+            throw isPutConflict(error) ? `Key ${key} already exists` : error;
+        }),
         onSuccess: () => refetch(),
         onError: (error: String) => {
             console.error("Failed to add etcd item:", error);
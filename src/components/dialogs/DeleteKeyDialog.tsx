@@ -11,11 +11,12 @@ import { useColorModeValue } from "../../components/ui/color-mode";
 import { HiX } from "react-icons/hi"
 import { useMutation } from "@tanstack/react-query";
 import { toaster } from "../ui/toaster";
-import { deleteEtcdItem } from "@/api/etcd";
+import { deleteEtcdItem, type Encoding } from "@/api/etcd";
 
 interface DeleteKeyDialogProps {
     keyToDelete: string;
     valueToDelete: string;
+    keyEncoding?: Encoding;
     onClose: () => void;
     refetch: () => void;
 }
@@ -23,11 +24,12 @@ interface DeleteKeyDialogProps {
 function DeleteKeyDialog({
     keyToDelete,
     valueToDelete,
+    keyEncoding,
     onClose,
     refetch
 }: DeleteKeyDialogProps) {
     const { mutateAsync, isPending } = useMutation<void, String, { key: string }>({
-        mutationFn: async ({ key }) => await deleteEtcdItem(key),
+        mutationFn: async ({ key }) => await deleteEtcdItem(key, keyEncoding),
         onSuccess: () => refetch(),
         onError: (error: String) => {
             toaster.create({ type: "error", title: "Delete Key Failed", description: error, closable: true });
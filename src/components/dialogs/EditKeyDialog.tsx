@@ -2,7 +2,7 @@ import { useState, useEffect, ChangeEvent } from "react";
 import { Button, CloseButton, Dialog, Field, Input, VStack, Box, Textarea, Text } from "@chakra-ui/react";
 import { codeInputProps } from "@/utils/inputProps";
 import { useColorModeValue } from "../../components/ui/color-mode";
-import { putEtcdItem } from "@/api/etcd";
+import { isPutConflict, putEtcdItem, type Encoding, type PutOptions } from "@/api/etcd";
 import { HiX } from "react-icons/hi";
 import { toaster } from "../ui/toaster";
 import { useMutation } from "@tanstack/react-query";
@@ -10,6 +10,9 @@ import { useMutation } from "@tanstack/react-query";
 interface EditKeyDialogProps {
     keyToEdit: string;
     valueToEdit: string;
+    keyEncoding?: Encoding;
+    valueEncoding?: Encoding;
+    modRevision?: number;
     onClose: () => void;
     refetch: () => void;
 }
@@ -17,17 +20,44 @@ interface EditKeyDialogProps {
 function EditKeyDialog({
     keyToEdit,
     valueToEdit,
+    keyEncoding,
+    valueEncoding,
+    modRevision,
     onClose,
     refetch
 }: EditKeyDialogProps) {
     const [dialogKey, setDialogKey] = useState(keyToEdit);
     const [dialogValue, setDialogValue] = useState(valueToEdit);
     const [isKeyEditable, setIsKeyEditable] = useState(false);
-    const { mutateAsync, isPending } = useMutation<void, String, { key: string, value: string }>({
-        mutationFn: async ({ key, value }) => await putEtcdItem(key, value),
+    const { mutateAsync, isPending } = useMutation<void, unknown, { key: string, value: string, overwrite?: boolean }>({
+        mutationFn: async ({ key, value, overwrite }) => {
+            const options: PutOptions = { key_encoding: keyEncoding, value_encoding: valueEncoding };
+            if (overwrite) {
+                options.overwrite = true;
+            } else {
+                // A renamed key must not clobber an existing one
+                options.expected_mod_revision = key === keyToEdit ? (modRevision ?? 0) : 0;
+            }
+            await putEtcdItem(key, value, options);
+        },
         onSuccess: () => refetch(),
-        onError: (error: String) => {
-            toaster.create({ type: "error", title: "Edit Key Failed", description: error, closable: true });
+        onError: (error, variables) => {
+            if (isPutConflict(error)) {
+                toaster.create({
+                    type: "warning",
+                    title: "Edit Conflict",
+                    description: error.current
+                        ? `${variables.key} was modified by someone else (now at revision ${error.current.mod_revision})`
+                        : `${variables.key} was created or deleted by someone else`,
+                    closable: true,
+                    action: {
+                        label: "Overwrite",
+                        onClick: () => { mutateAsync({ ...variables, overwrite: true }).then(onClose).catch(() => { }); },
+                    },
+                });
+                return;
+            }
+            toaster.create({ type: "error", title: "Edit Key Failed", description: String(error), closable: true });
         },
     });
 
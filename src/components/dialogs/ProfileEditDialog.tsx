@@ -1,4 +1,4 @@
-import { Profile, testConnection } from "@/api/etcd";
+import { Profile, TlsConfig, testConnection } from "@/api/etcd";
 import { Dialog, CloseButton, VStack, Box, Input, Flex, Field, Text, IconButton, Button, Switch } from "@chakra-ui/react";
 import { useState, useEffect } from "react";
 import { HiX } from "react-icons/hi";
@@ -22,6 +22,7 @@ function ProfileEditDialog({ profile, onSave, onCancel, loading, isNew }: Profil
     const [editedProfile, setEditedProfile] = useState<Profile>(profile);
     const [useAuth, setUseAuth] = useState(!!profile.user?.length);
     const [isLocked, setIsLocked] = useState(!!profile.locked);
+    const [useTls, setUseTls] = useState(!!profile.tls);
     const [testingConnection, setTestingConnection] = useState(false);
 
     // Add validation state
@@ -37,6 +38,7 @@ function ProfileEditDialog({ profile, onSave, onCancel, loading, isNew }: Profil
         setEditedProfile(profile);
         setUseAuth(!!profile.user?.length);
         setIsLocked(!!profile.locked);
+        setUseTls(!!profile.tls);
     }, [profile]);
 
     useEffect(() => {
@@ -55,6 +57,7 @@ function ProfileEditDialog({ profile, onSave, onCancel, loading, isNew }: Profil
         const finalProfile = {
             ...editedProfile,
             user: useAuth ? editedProfile.user : undefined,
+            tls: useTls ? (editedProfile.tls ?? {}) : undefined,
             locked: isLocked
         };
 
@@ -120,6 +123,16 @@ function ProfileEditDialog({ profile, onSave, onCancel, loading, isNew }: Profil
         });
     };
 
+    const updateTls = (field: keyof TlsConfig, value: string) => {
+        setEditedProfile({
+            ...editedProfile,
+            tls: {
+                ...editedProfile.tls,
+                [field]: value === "" ? undefined : value
+            }
+        });
+    };
+
     const handleTestConnection = async () => {
         // Only test if we have at least one endpoint
         if (editedProfile.endpoints.length === 0) return;
@@ -129,7 +142,8 @@ function ProfileEditDialog({ profile, onSave, onCancel, loading, isNew }: Profil
         // Prepare the profile with auth settings for testing
         const profileToTest = {
             ...editedProfile,
-            user: useAuth ? editedProfile.user : undefined
+            user: useAuth ? editedProfile.user : undefined,
+            tls: useTls ? (editedProfile.tls ?? {}) : undefined
         };
 
         try {
@@ -277,6 +291,65 @@ function ProfileEditDialog({ profile, onSave, onCancel, loading, isNew }: Profil
 
                             <Box borderTopWidth="1px" borderColor={borderColor} pt={4} mt={2} />
 
+                            <Flex align="center" justify="space-between">
+                                <Box>
+                                    <Text fontWeight="medium">TLS</Text>
+                                    <Text fontSize="sm" color="gray.500" mt={1}>
+                                        All endpoints are connected to over TLS when enabled, http:// endpoints are rejected
+                                    </Text>
+                                </Box>
+                                <Box as="label" display="flex" alignItems="center" cursor="pointer">
+                                    <Switch.Root checked={useTls} onCheckedChange={(e) => setUseTls(e.checked)}>
+                                        <Switch.HiddenInput />
+                                        <Switch.Control />
+                                        <Switch.Label ml={2}>{useTls ? 'Enabled' : 'Disabled'}</Switch.Label>
+                                    </Switch.Root>
+                                </Box>
+                            </Flex>
+
+                            {useTls && (
+                                <VStack gap={3} align="stretch">
+                                    <Box>
+                                        <Text fontWeight="medium" mb={1}>CA Certificate</Text>
+                                        <Input
+                                            {...codeInputProps}
+                                            value={editedProfile.tls?.ca_cert_path || ""}
+                                            onChange={(e) => updateTls('ca_cert_path', e.target.value)}
+                                            placeholder="Path to CA bundle (PEM), system roots if empty"
+                                        />
+                                    </Box>
+                                    <Box>
+                                        <Text fontWeight="medium" mb={1}>Client Certificate</Text>
+                                        <Input
+                                            {...codeInputProps}
+                                            value={editedProfile.tls?.client_cert_path || ""}
+                                            onChange={(e) => updateTls('client_cert_path', e.target.value)}
+                                            placeholder="Path to client certificate (PEM)"
+                                        />
+                                    </Box>
+                                    <Box>
+                                        <Text fontWeight="medium" mb={1}>Client Key</Text>
+                                        <Input
+                                            {...codeInputProps}
+                                            value={editedProfile.tls?.client_key_path || ""}
+                                            onChange={(e) => updateTls('client_key_path', e.target.value)}
+                                            placeholder="Path to client private key (PEM)"
+                                        />
+                                    </Box>
+                                    <Box>
+                                        <Text fontWeight="medium" mb={1}>Server Name</Text>
+                                        <Input
+                                            {...codeInputProps}
+                                            value={editedProfile.tls?.server_name || ""}
+                                            onChange={(e) => updateTls('server_name', e.target.value)}
+                                            placeholder="Override the name used to verify the server certificate"
+                                        />
+                                    </Box>
+                                </VStack>
+                            )}
+
+                            <Box borderTopWidth="1px" borderColor={borderColor} pt={4} mt={2} />
+
                             <Field.Root invalid={!!validationErrors.timeout}>
                                 <Field.Label>Timeout (ms)</Field.Label>
                                 <Input
@@ -331,7 +404,7 @@ function ProfileEditDialog({ profile, onSave, onCancel, loading, isNew }: Profil
                                         }
                                         setEditedProfile({ ...editedProfile, metrics_path: val });
                                     }}
-                                    placeholder="/metrics"
+                                    placeholder="Leave empty to disable metrics"
                                 />
                             </Field.Root>
 
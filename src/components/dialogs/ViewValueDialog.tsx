@@ -117,7 +117,7 @@ function ViewValueDialog({ keyToView, valueToView, item, onClose, onNavigate }:
         setLoadingHistory(true);
         try {
             // Fetch the version just before the current one
-            const res = await getKeyAtRevision(keyToView, currentHistoryItem.mod_revision - 1);
+            const res = await getKeyAtRevision(keyToView, currentHistoryItem.mod_revision - 1, item?.key_encoding);
             if (res) {
                 setHistoryStack([...historyStack, res]);
                 setHistoryIndex(nextIndex);
@@ -1,4 +1,5 @@
 import {
+    Alert,
     Box,
     Button,
     Card,
@@ -30,6 +31,7 @@ import {
     LuGlobe,
 } from "react-icons/lu";
 import { useClusterInfoQuery } from "../../hooks/useEtcdQuery";
+import { disarmAlarm, type AlarmInfo, type MemberStatus } from "../../api/etcd";
 import { formatBytes } from "@/utils/format";
 import { useActiveProfile } from "@/contexts/active-profile";
 
@@ -37,6 +39,57 @@ interface ClusterProps {
     configLoading: boolean;
 }
 
+function MemberStatusCell({ memberStatus, raftIndex }: { memberStatus?: MemberStatus; raftIndex: number }) {
+    if (!memberStatus) {
+        return <Text fontSize="sm" color="fg.muted">Unknown</Text>;
+    }
+
+    const { status, error, endpoint } = memberStatus;
+    if (!status) {
+        return (
+            <VStack align="start" gap={1}>
+                <Badge colorPalette="red" variant="solid" size="xs">Unreachable</Badge>
+                <Tooltip content={error}>
+                    <Text fontSize="xs" color="red.500" maxW="200px" truncate>
+                        {error}
+                    </Text>
+                </Tooltip>
+            </VStack>
+        );
+    }
+
+    // Raft entries not applied yet by this member, compared to the most advanced one
+    const lag = Math.max(0, raftIndex - status.raft_applied_index);
+    return (
+        <VStack align="start" gap={1}>
+            <HStack gap={1}>
+                <Badge colorPalette={status.errors.length > 0 ? "orange" : "green"} variant="solid" size="xs">
+                    {status.errors.length > 0 ? "Degraded" : "Healthy"}
+                </Badge>
+                {status.is_learner && (
+                    <Badge colorPalette="purple" variant="solid" size="xs">Learner</Badge>
+                )}
+                {lag > 0 && (
+                    <Badge colorPalette="yellow" variant="solid" size="xs">Lag {lag}</Badge>
+                )}
+            </HStack>
+            <Tooltip content={endpoint ?? undefined}>
+                <Text fontSize="xs" color="fg.muted">
+                    v{status.version} · {formatBytes(status.db_size)} ({formatBytes(status.db_size_in_use)} in use)
+                </Text>
+            </Tooltip>
+            <Text fontSize="xs" color="fg.muted">
+                Raft {status.raft_term}/{status.raft_index} · applied {status.raft_applied_index}
+            </Text>
+            {status.errors.map((err, idx) => (
+                <Text key={idx} fontSize="xs" color="orange.500">
+                    {err}
+                </Text>
+            ))}
+        </VStack>
+    );
+}
+
 function Cluster({ configLoading }: ClusterProps) {
     const { activeProfile } = useActiveProfile();
 
@@ -63,6 +116,22 @@ function Cluster({ configLoading }: ClusterProps) {
         }
     };
 
+    const handleDisarm = async (alarm: AlarmInfo) => {
+        try {
+            await disarmAlarm(alarm.hex_id, alarm.alarm);
+            toaster.success({
+                title: "Alarm disarmed",
+                description: `${alarm.alarm.toUpperCase()} alarm of member ${alarm.hex_id} disarmed`,
+            });
+        } catch (err) {
+            toaster.error({
+                title: "Disarm failed",
+                description: String(err),
+            });
+        }
+        refetch();
+    };
+
     const dbSize = formatBytes(clusterInfo?.db_size || 0);
 
     if (isFetching && !clusterInfo) {
@@ -106,6 +175,10 @@ function Cluster({ configLoading }: ClusterProps) {
     }
 
     const leaderMember = clusterInfo.members.find((m) => m.id === clusterInfo.leader);
+    const latestRaftIndex = Math.max(
+        clusterInfo.raft_index,
+        ...clusterInfo.member_statuses.map((s) => s.status?.raft_index ?? 0),
+    );
 
     return (
         <Flex direction="column" height="100vh">
@@ -144,6 +217,35 @@ function Cluster({ configLoading }: ClusterProps) {
                 {/* Content */}
                 <Box p={6}>
                     <VStack gap={6} align="stretch">
+                        {/* Active alarms block writes, so show them first */}
+                        {clusterInfo.alarms.map((alarm) => (
+                            <Alert.Root key={`${alarm.hex_id}-${alarm.alarm}`} status="error">
+                                <Alert.Indicator />
+                                <Alert.Content>
+                                    <Alert.Title>
+                                        {alarm.alarm.toUpperCase()} alarm on member {alarm.hex_id}
+                                    </Alert.Title>
+                                    <Alert.Description>
+                                        {alarm.alarm === "nospace"
+                                            ? "The member exceeded its space quota, writes are refused. Compact and defragment before disarming."
+                                            : "The member's data is inconsistent with the rest of the cluster."}
+                                    </Alert.Description>
+                                </Alert.Content>
+                                <Button size="sm" variant="outline" onClick={() => handleDisarm(alarm)}>
+                                    Disarm
+                                </Button>
+                            </Alert.Root>
+                        ))}
+                        {clusterInfo.alarms_error && (
+                            <Alert.Root status="warning">
+                                <Alert.Indicator />
+                                <Alert.Content>
+                                    <Alert.Title>Alarms could not be listed</Alert.Title>
+                                    <Alert.Description>{clusterInfo.alarms_error}</Alert.Description>
+                                </Alert.Content>
+                            </Alert.Root>
+                        )}
+
                         {/* Cluster Overview Stats */}
                         <Box>
                             <HStack mb={4} gap={2}>
@@ -421,6 +523,12 @@ function Cluster({ configLoading }: ClusterProps) {
                                                     <Text>Client URLs</Text>
                                                 </HStack>
                                             </Table.ColumnHeader>
+                                            <Table.ColumnHeader fontWeight="bold">
+                                                <HStack gap={2}>
+                                                    <Icon fontSize="sm"><LuNetwork /></Icon>
+                                                    <Text>Status</Text>
+                                                </HStack>
+                                            </Table.ColumnHeader>
                                         </Table.Row>
                                     </Table.Header>
                                     <Table.Body>
@@ -505,6 +613,12 @@ function Cluster({ configLoading }: ClusterProps) {
                                                         ))}
                                                     </VStack>
                                                 </Table.Cell>
+                                                <Table.Cell>
+                                                    <MemberStatusCell
+                                                        memberStatus={clusterInfo.member_statuses.find((s) => s.hex_id === member.hex_id)}
+                                                        raftIndex={latestRaftIndex}
+                                                    />
+                                                </Table.Cell>
                                             </Table.Row>
                                         ))}
                                     </Table.Body>
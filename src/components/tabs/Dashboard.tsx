@@ -249,7 +249,7 @@ function Dashboard({ configLoading }: DashboardProps) {
                           children={<TbEdit />}
                           variant="ghost"
                           size="sm"
-                          onClick={() => setDialogState({ action: "edit", key: item.key, value: item.value, })}
+                          onClick={() => setDialogState({ action: "edit", key: item.key, value: item.value, item })}
                         />
                       </Tooltip>
                       <Tooltip content="Delete key" showArrow>
@@ -259,7 +259,7 @@ function Dashboard({ configLoading }: DashboardProps) {
                           size="sm"
                           colorPalette="red"
                           variant="ghost"
-                          onClick={() => setDialogState({ action: "delete", key: item.key, value: item.value })}
+                          onClick={() => setDialogState({ action: "delete", key: item.key, value: item.value, item })}
                         />
                       </Tooltip>
                     </HStack>
@@ -393,6 +393,9 @@ function Dashboard({ configLoading }: DashboardProps) {
         <EditKeyDialog
           keyToEdit={dialogState.key}
           valueToEdit={dialogState.value}
+          keyEncoding={dialogState.item?.key_encoding}
+          valueEncoding={dialogState.item?.value_encoding}
+          modRevision={dialogState.item?.mod_revision}
           onClose={() => setDialogState(null)}
           refetch={() => {
             refetch();
@@ -405,6 +408,7 @@ function Dashboard({ configLoading }: DashboardProps) {
         <DeleteKeyDialog
           keyToDelete={dialogState.key}
           valueToDelete={dialogState.value}
+          keyEncoding={dialogState.item?.key_encoding}
           onClose={() => setDialogState(null)}
           refetch={refetch}
         />
@@ -65,6 +65,8 @@ const refreshIntervalCollection = createListCollection({
 
 const Metrics = ({ configLoading, isActive }: MetricsProps) => {
     const { activeProfile } = useActiveProfile();
+    // An unset metrics path turns metrics off for the profile
+    const metricsEnabled = !!activeProfile.metrics_path;
 
     const [selectedNode, setSelectedNode] = useState<Endpoint>(activeProfile.endpoints[0]);
     const [autoRefresh, setAutoRefresh] = useState(true);
@@ -94,6 +96,7 @@ const Metrics = ({ configLoading, isActive }: MetricsProps) => {
         currentProfileName: activeProfile.name,
         configLoading,
         endpoint: selectedNode,
+        metricsEnabled,
         isActive,
         autoRefresh,
         intervalMs: refreshInterval * 1000
@@ -283,7 +286,7 @@ const Metrics = ({ configLoading, isActive }: MetricsProps) => {
                             <Button
                                 onClick={() => { refetch() }}
                                 loading={isFetching}
-                                disabled={autoRefresh}
+                                disabled={autoRefresh || !metricsEnabled}
                                 size="md"
                                 variant="outline"
                             >
@@ -296,7 +299,23 @@ const Metrics = ({ configLoading, isActive }: MetricsProps) => {
 
                 {/* Content */}
                 <Box p={6}>
-                    {!metricsData && isFetching && !isError ? (
+                    {!metricsEnabled ? (
+                        <Flex direction="column" minH="60vh" align="center" justify="center">
+                            <EmptyState.Root>
+                                <EmptyState.Content>
+                                    <EmptyState.Indicator>
+                                        <LuServerCog size={48} />
+                                    </EmptyState.Indicator>
+                                    <VStack textAlign="center" gap={3}>
+                                        <EmptyState.Title>Metrics Disabled</EmptyState.Title>
+                                        <EmptyState.Description>
+                                            Set a metrics path on this profile to fetch metrics from its endpoints.
+                                        </EmptyState.Description>
+                                    </VStack>
+                                </EmptyState.Content>
+                            </EmptyState.Root>
+                        </Flex>
+                    ) : !metricsData && isFetching && !isError ? (
                         <Flex direction="column" align="center" justify="center" p={10}>
                             <Spinner size="xl" />
                             <Text mt={4}>Loading metrics...</Text>
@@ -60,14 +60,14 @@ export function useLazyValueEtcdItemsQuery({ enabled, keyPrefix, currentProfileN
     // Filter and paginate keys
     const filteredKeys = useMemo(() => {
         if (!searchQuery) return keysOnlyQuery.data || [];
-        return (keysOnlyQuery.data || []).filter(key => key.includes(searchQuery));
+        return (keysOnlyQuery.data || []).filter(k => k.key.includes(searchQuery));
     }, [keysOnlyQuery.data, searchQuery]);
     const paginatedKeys = useMemo(() => {
         const startIndex = (currentPage - 1) * pageSize;
         return filteredKeys.slice(startIndex, startIndex + pageSize);
     }, [filteredKeys, currentPage, pageSize]);
 
-    const pagedKeysSet = useMemo(() => new Set(paginatedKeys), [paginatedKeys]);
+    const pagedKeysSet = useMemo(() => new Set(paginatedKeys.map(k => `${k.key_encoding}:${k.key}`)), [paginatedKeys]);
 
     const valuesInRangeQuery = useQuery({
         queryKey: ["etcd-values-in-range", currentProfileName, paginatedKeys],
@@ -78,7 +78,7 @@ export function useLazyValueEtcdItemsQuery({ enabled, keyPrefix, currentProfileN
     const lazyLoadError = keysOnlyQuery.error ?? valuesInRangeQuery.error;
 
     return {
-        data: valuesInRangeQuery.data?.filter(item => pagedKeysSet.has(item.key)) || [],
+        data: valuesInRangeQuery.data?.filter(item => pagedKeysSet.has(`${item.key_encoding}:${item.key}`)) || [],
         total: filteredKeys.length,
         loadError: lazyLoadError ? (lazyLoadError.message || "Unknown error") : null,
         refetch: async () => { await keysOnlyQuery.refetch(); },
@@ -97,10 +97,11 @@ export function useClusterInfoQuery({ currentProfileName, configLoading }: {
     });
 }
 
-export function useMetricsQuery({ currentProfileName, configLoading, endpoint, isActive, autoRefresh, intervalMs = 10000 }: {
+export function useMetricsQuery({ currentProfileName, configLoading, endpoint, metricsEnabled, isActive, autoRefresh, intervalMs = 10000 }: {
     currentProfileName: string;
     configLoading: boolean;
     endpoint: Endpoint | null;
+    metricsEnabled: boolean;
     isActive: boolean;
     autoRefresh: boolean;
     intervalMs?: number;
@@ -115,6 +116,6 @@ export function useMetricsQuery({ currentProfileName, configLoading, endpoint, i
             return await fetchMetrics(endpoint);
         },
         refetchInterval: isActive && autoRefresh ? intervalMs : false,
-        enabled: isActive && !configLoading && !!currentProfileName && !!endpoint,
+        enabled: isActive && metricsEnabled && !configLoading && !!currentProfileName && !!endpoint,
     });
 }